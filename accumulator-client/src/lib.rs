@@ -8,6 +8,15 @@ use primitive_types::H256;
 pub struct UTXO {
     pub_key: H256,
     id: u64,
+    lock: Lock,
+}
+
+/// Mirrors the spending conditions of a UTXO in the runtime.
+#[derive(Default, Encode, Decode)]
+pub struct Lock {
+    timelock: u64,
+    hashlock: Option<H256>,
+    refund: Option<(H256, u64)>,
 }
 
 #[wasm_bindgen]
@@ -15,15 +24,33 @@ pub fn create_utxo(pub_key: &[u8], id: u64) -> UTXO {
     let result = UTXO {
         pub_key: H256::from_slice(pub_key),
         id,
+        lock: Lock::default(),
     };
     return result;
 }
 
+/// Creates a UTXO with spending conditions. An empty "hashlock" or "refund_key" means that the
+/// corresponding condition is not used.
+#[wasm_bindgen]
+pub fn create_locked_utxo(pub_key: &[u8], id: u64, timelock: u64, hashlock: &[u8], refund_key: &[u8], refund_height: u64) -> UTXO {
+    let lock = Lock {
+        timelock,
+        hashlock: if hashlock.is_empty() { None } else { Some(H256::from_slice(hashlock)) },
+        refund: if refund_key.is_empty() { None } else { Some((H256::from_slice(refund_key), refund_height)) },
+    };
+    return UTXO { pub_key: H256::from_slice(pub_key), id, lock };
+}
+
 #[wasm_bindgen]
 pub fn get_utxo_elem(pub_key: &[u8], id: u64) -> Vec<u8> {
     return create_utxo(pub_key, id).encode();
 }
 
+#[wasm_bindgen]
+pub fn get_locked_utxo_elem(pub_key: &[u8], id: u64, timelock: u64, hashlock: &[u8], refund_key: &[u8], refund_height: u64) -> Vec<u8> {
+    return create_locked_utxo(pub_key, id, timelock, hashlock, refund_key, refund_height).encode();
+}
+
 #[wasm_bindgen]
 pub fn hash_to_prime(elem: &[u8]) -> Vec<u8> {
     let mut result: [u8; 256] = [0; 256];  // Change this constant
@@ -43,7 +70,7 @@ pub fn get_witness(old_state: &[u8], agg: &[u8], elem: &[u8]) -> Vec<u8> {
 pub fn update_witness(elem: &[u8], witness: &[u8], new_state: &[u8], added: &[u8], deleted: &[u8]) -> Vec<u8> {
     let mut result: [u8; 256] = [0; 256];
     witnesses::update_mem_wit(U2048::from_little_endian(elem), U2048::from_little_endian(witness), U2048::from_little_endian(new_state),
-                              U2048::from_little_endian(added), U2048::from_little_endian(deleted)).to_little_endian(&mut result);
+                              U2048::from_little_endian(added), U2048::from_little_endian(deleted)).unwrap().to_little_endian(&mut result);
     return result.to_vec();
 }
 
@@ -57,8 +84,9 @@ mod tests {
         let utxo = UTXO {
             pub_key: H256::from_slice(hex::decode("d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d").unwrap()),
             id: 0,
+            lock: Lock::default(),
         };
-        assert_eq!(subroutines::hash_to_prime(&utxo.encode()), U2048::from_dec_str("2882671871935824533").unwrap());
+        assert_eq!(subroutines::hash_to_prime(&utxo.encode()), U2048::from_dec_str("146484181").unwrap());
     }
 
 }
\ No newline at end of file
@@ -10,6 +10,7 @@ extern crate uint;
 pub mod subroutines;
 pub mod proofs;
 pub mod witnesses;
+pub mod verifier;
 
 /// Construct BigInt type.
 construct_uint! {
@@ -17,10 +18,39 @@ construct_uint! {
 	pub struct U2048(32);
 }
 
+/// Serializes the integer as a 0x-prefixed hex string(like the hashes of primitive-types) so that
+/// it can be used in the genesis config, the chain spec and the RPC interface of the node.
+#[cfg(feature = "std")]
+impl serde::Serialize for U2048 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&format!("{:#x}", self));
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> serde::Deserialize<'de> for U2048 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        let hex = if value.starts_with("0x") { &value[2..] } else { &value[..] };
+        // Longer strings would overflow the 256 bytes of the integer.
+        if hex.is_empty() || hex.len() > 512 {
+            return Err(serde::de::Error::custom("Invalid length of U2048 hex string."));
+        }
+        return hex.parse().map_err(|_| serde::de::Error::custom("Invalid U2048 hex string."));
+    }
+}
+
 /// Defines the RSA group. Arbitrary set at MODULUS = 13 for testing.
 /// Example (insecure) modulus -> RSA 100: "1522605027922533360535618378132637429718068114961380688657908494580122963258952897654000350692006139"
+#[cfg(not(feature = "rsa-768"))]
 pub const MODULUS: &str = "13";
 
+/// The RSA-768 modulus, which is used to test that witnesses cannot be forged. Its factorization is
+/// public, so it is not secure either. Moduli of more than 1024 bits overflow the intermediate products
+/// of "subroutines::shamir_trick".
+#[cfg(feature = "rsa-768")]
+pub const MODULUS: &str = "1230186684530117755130494958384962720772853569595334792197322452151726400507263657518745202199786469389956474942774063845925192557326303453731548268507917026122142913461670429214311602221240479274737794080665351419597459856902143413";
+
 /// Security parameter that represents the size of elements added to the accumulator.
 pub const LAMBDA: u32 = u32::max_value();
 
@@ -86,3 +116,23 @@ pub fn batch_add(state: U2048, elems: &Vec<U2048>) -> (U2048, U2048, U2048) {
     let proof = proofs::poe(state, x_agg, new_state);
     return (new_state, x_agg, proof);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde() {
+        let elem = U2048::from_dec_str("1522605027922533360535618378132637429718068114961380688657908494580122963258952897654000350692006139").unwrap();
+        let json = serde_json::to_string(&elem).unwrap();
+        assert_eq!(serde_json::from_str::<U2048>(&json).unwrap(), elem);
+        assert_eq!(serde_json::to_string(&U2048::from(255)).unwrap(), "\"0xff\"");
+        assert_eq!(serde_json::to_string(&U2048::from(0)).unwrap(), "\"0x0\"");
+        assert_eq!(serde_json::from_str::<U2048>("\"0x0d\"").unwrap(), U2048::from(13));
+        assert_eq!(serde_json::from_str::<U2048>("\"d\"").unwrap(), U2048::from(13));
+        assert_eq!(serde_json::from_str::<U2048>("\"0xzz\"").is_err(), true);
+        assert_eq!(serde_json::from_str::<U2048>("\"0x\"").is_err(), true);
+        assert_eq!(serde_json::from_str::<U2048>(&format!("\"0x1{}\"", "0".repeat(512))).is_err(), true);
+        assert_eq!(serde_json::from_str::<U2048>(&format!("\"0x{}\"", "f".repeat(512))).unwrap(), U2048::max_value());
+    }
+}
@@ -0,0 +1,65 @@
+/// Accumulator Transition Verification
+/// Re-checks the proofs of exponentiation produced by "batch_delete" and "batch_add" so that an
+/// observer who does not execute the chain can audit each update of the accumulator.
+
+use crate::proofs;
+use super::U2048;
+
+/// Verifies a batch deletion given the previous state and the (new_state, agg, proof) triple
+/// returned by "batch_delete", i.e. that new_state^agg = prev_state.
+pub fn verify_deletion(prev_state: U2048, deletion: (U2048, U2048, U2048)) -> bool {
+    let (new_state, agg, proof) = deletion;
+    return proofs::verify_poe(new_state, agg, prev_state, proof);
+}
+
+/// Verifies a batch addition given the previous state and the (new_state, agg, proof) triple
+/// returned by "batch_add", i.e. that prev_state^agg = new_state.
+pub fn verify_addition(prev_state: U2048, addition: (U2048, U2048, U2048)) -> bool {
+    let (new_state, agg, proof) = addition;
+    return proofs::verify_poe(prev_state, agg, new_state, proof);
+}
+
+/// Verifies an optional batch deletion followed by an optional batch addition (the order used on
+/// block finalization). Returns the resulting state or `None` if one of the proofs is invalid.
+pub fn verify_transition(prev_state: U2048, deletion: Option<(U2048, U2048, U2048)>,
+                         addition: Option<(U2048, U2048, U2048)>) -> Option<U2048> {
+    let mut state = prev_state;
+    if let Some(deletion) = deletion {
+        if !verify_deletion(state, deletion) {
+            return None;
+        }
+        state = deletion.0;
+    }
+    if let Some(addition) = addition {
+        if !verify_addition(state, addition) {
+            return None;
+        }
+        state = addition.0;
+    }
+    return Some(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{batch_add, batch_delete, witnesses};
+
+    #[test]
+    fn test_verify_transition() {
+        let elems = vec![U2048::from(3), U2048::from(5), U2048::from(7)];
+        let witnesses = witnesses::create_all_mem_wit(U2048::from(2), &elems);
+        let (state, _, _) = batch_add(U2048::from(2), &elems);
+
+        let deletion = batch_delete(state, &vec![(elems[0], witnesses[0]), (elems[1], witnesses[1])]);
+        let addition = batch_add(deletion.0, &vec![U2048::from(11), U2048::from(17)]);
+        assert_eq!(verify_deletion(state, deletion), true);
+        assert_eq!(verify_addition(deletion.0, addition), true);
+        assert_eq!(verify_transition(state, Some(deletion), Some(addition)), Some(addition.0));
+        assert_eq!(verify_transition(state, None, None), Some(state));
+
+        // Tampered aggregates are rejected.
+        let (_, agg, proof) = addition;
+        assert_eq!(verify_addition(deletion.0, (addition.0, agg + U2048::from(2), proof)), false);
+        assert_eq!(verify_transition(state, Some((deletion.0, deletion.1 * U2048::from(19), deletion.2)), None), None);
+    }
+}
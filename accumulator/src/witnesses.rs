@@ -6,11 +6,11 @@ use rstd::prelude::Vec;
 use super::U2048;
 
 /// Given an old state, the product of a set of elements that have been added, and a single element from that
-/// set, returns the witness for that element.
+/// set, returns the witness for that element. Returns None if "elem" is zero or does not divide "agg".
 /// NOTE: "old_state" represents the state *before* the elements are added.
 /// This function will likely be used by an online user.
 pub fn mem_wit_create(old_state: U2048, agg: U2048, elem: U2048) -> Option<U2048> {
-    if agg % elem != U2048::from(0) {
+    if elem == U2048::from(0) || agg % elem != U2048::from(0) {
         return None;
     }
     let quotient = agg / elem;
@@ -26,18 +26,45 @@ pub fn verify_mem_wit(state: U2048, witness: U2048, elem: U2048) -> bool {
 /// Updates a membership witness based on untracked additions and deletions. Algorithm is based on
 /// section 3.2 of the paper titled "Dynamic Accumulators and Applications to Efficient Revocation of
 /// Anonymous Credentials". Note that "additions" represent the product of the added elements
-/// and "deletions" represents the product of the deleted elements.
-/// NOTE: Does not do any error checking on unwrap.
-pub fn update_mem_wit(elem: U2048, mut witness: U2048, new_state: U2048, additions: U2048, deletions: U2048) -> U2048 {
+/// and "deletions" represents the product of the deleted elements. Returns None if the deleted elements
+/// are not coprime to "elem"(e.g. if "elem" itself has been deleted).
+pub fn update_mem_wit(elem: U2048, mut witness: U2048, new_state: U2048, additions: U2048, deletions: U2048) -> Option<U2048> {
     // Handle added elems
     witness = subroutines::mod_exp(witness, additions, U2048::from_dec_str(super::MODULUS).unwrap());
 
     // Handle deleted elems
-    witness = subroutines::shamir_trick(witness, new_state, elem, deletions).unwrap();
-    return witness;
+    return subroutines::shamir_trick(witness, new_state, elem, deletions);
 }
 
 
+/// Merges a sequence of per-block deltas into a single delta that can be passed to "update_mem_wit".
+/// Each delta is given as (additions, deletions, new_state) and the deltas must be ordered by block.
+/// Elements that are added and later deleted within the sequence cancel out. Returns `None` if no
+/// deltas are given or if an element is deleted and then added again(e.g. repeated mints of the same
+/// value). Cancelling such an element would hide the deletion, so a witness of the deleted element
+/// would still be updated as if it had never been spent. These ranges must be applied block by block.
+/// Also returns `None` if the product of the merged additions or deletions does not fit in a U2048.
+pub fn merge_deltas(deltas: &[(U2048, U2048, U2048)]) -> Option<(U2048, U2048, U2048)> {
+    let (_, _, new_state) = *deltas.last()?;
+    let (mut additions, mut deletions) = (U2048::from(1), U2048::from(1));
+    for &(block_additions, block_deletions, _) in deltas {
+        // Within a block, elements are deleted before the new elements are added. Cancel out the
+        // elements that have been added by a previous block and are deleted by this one.
+        deletions = deletions.checked_mul(block_deletions)?;
+        let (gcd, _) = subroutines::extended_gcd(additions, deletions);
+        additions = additions / gcd;
+        deletions = deletions / gcd;
+
+        // Reject elements that are added again after they have been deleted.
+        let (gcd, _) = subroutines::extended_gcd(deletions, block_additions);
+        if gcd != U2048::from(1) {
+            return None;
+        }
+        additions = additions.checked_mul(block_additions)?;
+    }
+    return Some((additions, deletions, new_state));
+}
+
 /// Takes two elements + membership witnesses and returns the aggregated witness and aggregated proof.
 /// NOTE: Does very little error checking (Ex: Does not do any error checking on unwrap).
 pub fn agg_mem_wit(state: U2048, witness_x: U2048, witness_y: U2048, x: U2048, y: U2048) -> (U2048, U2048) {
@@ -117,6 +144,7 @@ mod tests {
         assert_eq!(mem_wit_create(U2048::from(2), U2048::from(1155), U2048::from(7)).unwrap(), U2048::from(5));
         assert_eq!(mem_wit_create(U2048::from(2), U2048::from(1155),U2048::from(11)).unwrap(), U2048::from(5));
         assert_eq!(mem_wit_create(U2048::from(2), U2048::from(1155),U2048::from(4)).is_none(), true);
+        assert_eq!(mem_wit_create(U2048::from(2), U2048::from(1155),U2048::from(0)).is_none(), true);
     }
 
     #[test]
@@ -142,7 +170,77 @@ mod tests {
         let witness = U2048::from(8);
         let new_state = U2048::from(11);
 
-        assert_eq!(update_mem_wit(elem, witness, new_state, additions, deletions), U2048::from(6));
+        assert_eq!(update_mem_wit(elem, witness, new_state, additions, deletions), Some(U2048::from(6)));
+
+        // The witness of a deleted element cannot be updated.
+        assert_eq!(update_mem_wit(U2048::from(5), witness, new_state, additions, deletions), None);
+    }
+
+    #[test]
+    fn test_merge_deltas() {
+        assert_eq!(merge_deltas(&[]), None);
+
+        // Block 1 adds 3 and 5, block 2 deletes 3 and 19 and adds 7, block 3 deletes 7 and adds 11.
+        let modulus = U2048::from_dec_str(crate::MODULUS).unwrap();
+        let state = |exp: u64| subroutines::mod_exp(U2048::from(2), U2048::from(exp), modulus);
+        let deltas = vec![
+            (U2048::from(15), U2048::from(1), state(17 * 19 * 3 * 5)),
+            (U2048::from(7), U2048::from(57), state(17 * 5 * 7)),
+            (U2048::from(11), U2048::from(7), state(17 * 5 * 11)),
+        ];
+
+        let (additions, deletions, new_state) = merge_deltas(&deltas).unwrap();
+        assert_eq!(additions, U2048::from(55));
+        assert_eq!(deletions, U2048::from(19));
+        assert_eq!(new_state, state(17 * 5 * 11));
+
+        // The witness of 17 before block 1 can be updated in a single step.
+        let witness = update_mem_wit(U2048::from(17), state(19), new_state, additions, deletions).unwrap();
+        assert_eq!(verify_mem_wit(new_state, witness, U2048::from(17)), true);
+
+        // An element that is added twice and deleted once remains in the accumulator.
+        let deltas = vec![
+            (U2048::from(5), U2048::from(1), state(17 * 5)),
+            (U2048::from(5), U2048::from(1), state(17 * 25)),
+            (U2048::from(1), U2048::from(5), state(17 * 5)),
+        ];
+        assert_eq!(merge_deltas(&deltas), Some((U2048::from(5), U2048::from(1), state(17 * 5))));
+    }
+
+    #[test]
+    fn test_merge_deltas_readded() {
+        // 5 is accumulated before block 1, deleted by block 2 and minted again by block 3. The merged
+        // delta would cancel the deletion, so the range cannot be merged.
+        let modulus = U2048::from_dec_str(crate::MODULUS).unwrap();
+        let state = |exp: u64| subroutines::mod_exp(U2048::from(2), U2048::from(exp), modulus);
+        let deltas = vec![
+            (U2048::from(11), U2048::from(1), state(17 * 5 * 11)),
+            (U2048::from(7), U2048::from(5), state(17 * 11 * 7)),
+            (U2048::from(5), U2048::from(1), state(17 * 11 * 7 * 5)),
+        ];
+        assert_eq!(merge_deltas(&deltas), None);
+
+        // The same holds if the element is deleted and added again within a single block.
+        assert_eq!(merge_deltas(&[(U2048::from(5), U2048::from(5), state(17 * 5))]), None);
+
+        // Elements that are added within the range and deleted afterwards can still be re-added.
+        let (additions, deletions, _) = merge_deltas(&deltas[2..]).unwrap();
+        assert_eq!((additions, deletions), (U2048::from(5), U2048::from(1)));
+        let readded = vec![deltas[2], (U2048::from(1), U2048::from(5), state(17 * 11 * 7)), deltas[2]];
+        assert_eq!(merge_deltas(&readded), Some((U2048::from(5), U2048::from(1), state(17 * 11 * 7 * 5))));
+
+        // The deltas can still be merged up to the block that adds the element again.
+        assert_eq!(merge_deltas(&deltas[..2]), Some((U2048::from(77), U2048::from(5), state(17 * 11 * 7))));
+    }
+
+    #[test]
+    fn test_merge_deltas_overflow() {
+        // Each block adds or deletes a product of about 1100 bits, so two of them do not fit in a U2048.
+        let large = U2048::from(3).pow(U2048::from(700));
+        let state = U2048::from(2);
+        assert_eq!(merge_deltas(&[(large, U2048::from(1), state)]), Some((large, U2048::from(1), state)));
+        assert_eq!(merge_deltas(&[(large, U2048::from(1), state), (large, U2048::from(1), state)]), None);
+        assert_eq!(merge_deltas(&[(U2048::from(1), large, state), (U2048::from(1), large, state)]), None);
     }
 
     #[test]
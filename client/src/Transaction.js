@@ -5,6 +5,7 @@ import { TxButton } from './substrate-lib/components';
 import keyring from '@polkadot/ui-keyring';
 import { bnToU8a } from '@polkadot/util';
 import { U8a } from '@polkadot/types/codec';
+import { createType } from '@polkadot/types';
 
 export default function Transaction (props) {
   const { api } = useSubstrate();
@@ -28,18 +29,18 @@ export default function Transaction (props) {
   }, [transaction]);
 
   function createTransaction () {
-    const sender = keyring.decodeAddress(accountPair.address, true);
-    const receiver = keyring.decodeAddress(address, true);
-    const idNum = BigInt(ID);
-
-    const input = { sender, idNum };
-    const output = { receiver, idNum };
+    const id = BigInt(ID);
+    const lock = { timelock: 0, hashlock: null, refund: null };
+    const input = { pub_key: keyring.decodeAddress(accountPair.address, true), id, lock };
+    const output = { pub_key: keyring.decodeAddress(address, true), id, lock };
 
     const newWitness = new U8a(bnToU8a(BigInt(witness), 2048, true));
 
-    const signature = accountPair.sign(idNum);
+    // The owner of the input signs the SCALE encoded (input, output) pair.
+    const payload = createType('(UTXO, UTXO)', [input, output]).toU8a();
+    const signature = accountPair.sign(payload);
 
-    const tx = { input, output, newWitness, signature };
+    const tx = { input, output, witness: newWitness, signature, preimage: [], multisig: null };
     setFormState(formState => ({ ...formState, transaction: tx }));
     alert('Transaction created! Ready to submit to the blockchain.');
   }
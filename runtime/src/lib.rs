@@ -26,6 +26,7 @@ use client::{
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
 use version::NativeVersion;
+use accumulator::U2048;
 
 // A few exports that help ease life for downstream crates.
 #[cfg(any(feature = "std", test))]
@@ -62,7 +63,10 @@ pub type Hash = primitives::H256;
 pub type DigestItem = generic::DigestItem<Hash>;
 
 /// Used for the module template in `./stateless.rs`
-mod stateless;
+pub mod stateless;
+
+/// Swaps between the stateless UTXOs and the StatelessAccounts balances in `./swap.rs`
+pub mod swap;
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -97,7 +101,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
     spec_name: create_runtime_str!("stateless-blockchain"),
     impl_name: create_runtime_str!("stateless-blockchain"),
     authoring_version: 3,
-    spec_version: 4,
+    spec_version: 5,
     impl_version: 4,
     apis: RUNTIME_API_VERSIONS,
 };
@@ -253,7 +257,9 @@ impl sudo::Trait for Runtime {
 }
 
 parameter_types! {
-    pub const KeySpace: u8 = 255;
+    pub const KeySpace: vector_commitment::Key = vector_commitment::Key::max_value();
+    pub const CheckpointWindow: u64 = DAYS as u64;
+    pub const UtxoValue: vector_commitment::Balance = 1;
 }
 
 /// Used for the module template in `./stateless.rs`
@@ -264,6 +270,12 @@ impl stateless::Trait for Runtime {
 impl vector_commitment::Trait for Runtime {
     type Event = Event;
     type KeySpace = KeySpace;
+    type CheckpointWindow = CheckpointWindow;
+}
+
+impl swap::Trait for Runtime {
+    type Event = Event;
+    type UtxoValue = UtxoValue;
 }
 
 construct_runtime!(
@@ -279,8 +291,9 @@ pub enum Runtime where
     Indices: indices::{default, Config<T>},
     Balances: balances,
     Sudo: sudo,
-    Stateless: stateless::{Module, Call, Storage, Event},
+    Stateless: stateless::{Module, Call, Storage, Config, Event},
     StatelessAccounts: vector_commitment::{Module, Call, Storage, Event<T>},
+    Swap: swap::{Module, Call, Event},
 
 }
 );
@@ -356,7 +369,13 @@ impl block_builder_api::BlockBuilder<Block> for Runtime {
 
 impl client_api::TaggedTransactionQueue<Block> for Runtime {
     fn validate_transaction(tx: <Block as BlockT>::Extrinsic) -> TransactionValidity {
-        Executive::validate_transaction(tx)
+        let validity = Executive::validate_transaction(tx.clone())?;
+        // Check the signatures and witnesses of spends against the current state of the accumulator
+        match tx.function {
+            Call::Stateless(ref call) => Ok(validity.combine_with(Stateless::validate_call(call)?)),
+            Call::Swap(ref call) => Ok(validity.combine_with(Swap::validate_call(call)?)),
+            _ => Ok(validity),
+        }
     }
 }
 
@@ -410,6 +429,32 @@ impl babe_primitives::BabeApi<Block> for Runtime {
     }
 }
 
+impl stateless::StatelessApi<Block> for Runtime {
+    fn get_state() -> U2048 {
+        Stateless::get_state()
+    }
+
+    fn get_modulus() -> U2048 {
+        Stateless::get_modulus()
+    }
+
+    fn get_generator() -> U2048 {
+        Stateless::get_generator()
+    }
+
+    fn get_spent_coins() -> Vec<(U2048, U2048)> {
+        Stateless::get_spent_coins()
+    }
+
+    fn get_new_coins() -> Vec<U2048> {
+        Stateless::get_new_coins()
+    }
+
+    fn verify_witness(elem: U2048, witness: U2048) -> bool {
+        Stateless::verify_witness(elem, witness)
+    }
+}
+
 impl substrate_session::SessionKeys<Block> for Runtime {
     fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
         let seed = seed.as_ref().map(|s| rstd::str::from_utf8(&s).expect("Seed is an utf8 string"));
@@ -13,28 +13,251 @@
 
 use support::{decl_module, decl_storage, decl_event, ensure, StorageValue, dispatch::Result, traits::Get};
 use system::ensure_signed;
-use primitive_types::H256;
+use primitive_types::{H256, H512};
+use primitives::{sr25519, offchain::StorageKind};
+use sr_primitives::generic::DigestItem;
+use sr_primitives::traits::{Verify, SaturatedConversion};
+use sr_primitives::transaction_validity::{TransactionValidity, ValidTransaction, InvalidTransaction};
 use rstd::prelude::Vec;
 use rstd::vec;
 use codec::{Encode, Decode};
 use accumulator::*;
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
 
 /// At the moment, this particular struct resembles more closely an NFT.
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 #[derive(Default, Clone, Encode, Decode, PartialEq, Eq, Copy)]
 pub struct UTXO {
-    pub_key: H256,
-    id: u64,
+    /// The public key of the owner or the hash of the multisig policy(see `MultisigPolicy::hash`).
+    pub pub_key: H256,
+    pub id: u64,
+    /// Since the lock is part of the accumulated element, witnesses also bind to the conditions.
+    #[cfg_attr(feature = "std", serde(default))]
+    pub lock: Lock,
+}
+
+/// Spending conditions of a UTXO. The default lock does not impose any condition besides the
+/// signature of the owner. A hashlock combined with a refund is a hashed time-locked contract(HTLC).
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[derive(Default, Clone, Encode, Decode, PartialEq, Eq, Copy)]
+pub struct Lock {
+    /// Block number from which the owner can spend the UTXO.
+    pub timelock: u64,
+    /// blake2_256 hash of the preimage that the owner must reveal in order to spend the UTXO.
+    pub hashlock: Option<H256>,
+    /// Key that can spend the UTXO without revealing the preimage from the given block number onward.
+    pub refund: Option<(H256, u64)>,
+}
+
+/// An m-of-n multisig policy. A UTXO is locked to a policy by using the hash of the policy as its
+/// "pub_key", so the policy is bound to the accumulated element without enlarging it.
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[derive(Default, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct MultisigPolicy {
+    /// The number of signatures required to spend the UTXO.
+    pub threshold: u32,
+    pub keys: Vec<H256>,
+}
+
+impl MultisigPolicy {
+    /// Returns the blake2_256 hash of the encoded policy.
+    pub fn hash(&self) -> H256 {
+        return H256::from(runtime_io::blake2_256(&self.encode()));
+    }
+
+    /// Verifies that at least "threshold" distinct keys of the policy have signed the payload. The
+    /// signatures must be sorted by the index of the key within the policy.
+    pub fn verify(&self, signatures: &[(u32, H512)], payload: &[u8]) -> bool {
+        if self.threshold == 0 || self.threshold as usize > self.keys.len() || self.keys.len() > MAX_MULTISIG_KEYS {
+            return false;
+        }
+        if signatures.len() < self.threshold as usize {
+            return false;
+        }
+        let mut last_index: Option<u32> = None;
+        for (index, signature) in signatures.iter() {
+            // Strictly increasing indices ensure that every signature comes from a different key.
+            if last_index.map_or(false, |last| *index <= last) || *index as usize >= self.keys.len() {
+                return false;
+            }
+            if !verify_signature(signature, &self.keys[*index as usize], payload) {
+                return false;
+            }
+            last_index = Some(*index);
+        }
+        return true;
+    }
+}
+
+/// The policy of a multisig input along with the signatures of its co-owners.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Default, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct MultisigSpend {
+    pub policy: MultisigPolicy,
+    /// Pairs of the index of a key within the policy and its signature over the "signing_payload".
+    pub signatures: Vec<(u32, H512)>,
+}
+
+/// Verifies an sr25519 signature of the given public key.
+pub fn verify_signature(signature: &H512, pub_key: &H256, payload: &[u8]) -> bool {
+    let signature = sr25519::Signature::from_raw(signature.to_fixed_bytes());
+    return signature.verify(payload, &sr25519::Public::from_raw(pub_key.to_fixed_bytes()));
+}
+
+/// The key that signed a transaction.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Spender {
+    Owner,
+    Refund,
 }
 
 /// Primitive transaction model with one input and one output.
 #[cfg_attr(feature = "std", derive(Debug))]
 #[derive(Default, Clone, Encode, Decode, PartialEq, Eq)]
 pub struct Transaction {
-    input: UTXO,
-    output: UTXO,
-    witness: Vec<u8>,
-    // Would in practice include a signature here.
+    pub input: UTXO,
+    pub output: UTXO,
+    pub witness: Vec<u8>,
+    /// sr25519 signature of the owner(or the refund key) of the input over the "signing_payload".
+    pub signature: H512,
+    /// Preimage of the hashlock of the input. Empty if the input does not have a hashlock.
+    pub preimage: Vec<u8>,
+    /// Replaces the signature of the owner if the input is locked to a multisig policy.
+    pub multisig: Option<MultisigSpend>,
+}
+
+impl Transaction {
+    /// Returns the payload that must be signed by the owner of the input.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        return (self.input, self.output).encode();
+    }
+
+    /// Returns whether the transaction has been signed by the owner(or the co-owners of a multisig
+    /// input) or the refund key of the input.
+    pub fn signer(&self) -> Option<Spender> {
+        let payload = self.signing_payload();
+        let signed_by_owner = match &self.multisig {
+            Some(multisig) => {
+                multisig.policy.hash() == self.input.pub_key && multisig.policy.verify(&multisig.signatures, &payload)
+            },
+            None => verify_signature(&self.signature, &self.input.pub_key, &payload),
+        };
+        if signed_by_owner {
+            return Some(Spender::Owner);
+        }
+        if let Some((refund_key, _)) = self.input.lock.refund {
+            if verify_signature(&self.signature, &refund_key, &payload) {
+                return Some(Spender::Refund);
+            }
+        }
+        return None;
+    }
+
+    /// Checks the lock of the input at the given block number. The owner must wait for the timelock
+    /// and reveal the preimage of the hashlock while the refund key must wait for the refund height.
+    pub fn is_unlocked(&self, spender: Spender, block_number: u64) -> bool {
+        let lock = &self.input.lock;
+        match spender {
+            Spender::Owner => {
+                if block_number < lock.timelock {
+                    return false;
+                }
+                return match lock.hashlock {
+                    Some(hash) => H256::from(runtime_io::blake2_256(&self.preimage)) == hash,
+                    None => true,
+                };
+            },
+            Spender::Refund => {
+                return lock.refund.map_or(false, |(_, height)| block_number >= height);
+            },
+        }
+    }
+
+    /// Verifies the signature and the lock of the input at the given block number.
+    pub fn verify_spend(&self, block_number: u64) -> rstd::result::Result<(), &'static str> {
+        let spender = self.signer().ok_or("Signature is invalid.")?;
+        ensure!(self.is_unlocked(spender, block_number), "Coin is locked.");
+        Ok(())
+    }
+}
+
+/// A call of a block that updates the accumulator. Stateless validators only know the outcome of the
+/// checks of this module, so the calls are kept in the order of the block.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Encode, Decode, PartialEq, Eq)]
+pub enum StatelessCall {
+    /// A spend through "addTransaction".
+    Spend(Transaction),
+    /// A coin created through "mint".
+    Mint(u64),
+    /// A UTXO burned by a swap along with its witness. The signature of the swap must already be
+    /// checked. Whether it has been burned also depends on the balances of the accounts.
+    Burn(UTXO, Vec<u8>),
+    /// A UTXO created by a swap. Whether it has been created depends on the balances of the accounts.
+    Swapped(UTXO),
+}
+
+/// The contents of a block required by a stateless validator, which only keeps the state of the
+/// accumulator. The spends carry their own witnesses and the transition is proven by the digest.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Encode, Decode, PartialEq, Eq)]
+pub struct StatelessBlock {
+    /// The number of the block, which the locks of the spent coins are checked against.
+    pub number: u64,
+    pub calls: Vec<StatelessCall>,
+    pub digest: AccumulatorDigest,
+}
+
+/// Upper bound on the number of keys of a multisig policy.
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Custom error code for spends to the owner of the input.
+pub const SELF_TRANSFER: u8 = 0;
+
+/// Custom error code for spends of inputs whose conditions are not met.
+pub const LOCKED: u8 = 1;
+
+/// Prefix of the cached primes within the persistent off-chain local storage.
+pub const PRIME_CACHE_PREFIX: &[u8] = b"stateless_prime";
+
+/// Identifies the accumulator digest among the other `DigestItem::Other` items of a header.
+pub const ACCUMULATOR_DIGEST_ID: [u8; 4] = *b"accu";
+
+/// Accumulator transition committed in the header digest of a block. Includes the proofs of
+/// exponentiation of the batch deletion and addition so that light clients can verify the
+/// transition without executing the block.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Encode, Decode, PartialEq, Eq)]
+pub struct AccumulatorDigest {
+    /// State of the accumulator after the block.
+    pub state: U2048,
+    /// The state after deletion, the product of the deleted elements and the proof of exponentiation.
+    pub deletion: Option<(U2048, U2048, U2048)>,
+    /// The state after addition, the product of the added elements and the proof of exponentiation.
+    pub addition: Option<(U2048, U2048, U2048)>,
+}
+
+impl AccumulatorDigest {
+    /// Wraps the digest in a header digest item.
+    pub fn to_digest_item<Hash>(&self) -> DigestItem<Hash> {
+        return DigestItem::Other((ACCUMULATOR_DIGEST_ID, self).encode());
+    }
+
+    /// Extracts the accumulator digest from a header digest item if it contains one.
+    pub fn from_digest_item<Hash>(item: &DigestItem<Hash>) -> Option<Self> {
+        if let DigestItem::Other(data) = item {
+            if data.starts_with(&ACCUMULATOR_DIGEST_ID) {
+                return Decode::decode(&mut &data[ACCUMULATOR_DIGEST_ID.len()..]).ok();
+            }
+        }
+        return None;
+    }
+
+    /// Verifies the transition from the state of the previous block using the proofs of exponentiation.
+    pub fn verify(&self, prev_state: U2048) -> bool {
+        return verifier::verify_transition(prev_state, self.deletion, self.addition) == Some(self.state);
+    }
 }
 
 pub trait Trait: system::Trait {
@@ -43,10 +266,129 @@ pub trait Trait: system::Trait {
 
 decl_storage! {
     trait Store for Module<T: Trait> as Stateless {
-        State get(get_state): U2048 = U2048::from(2);  // Use 2 as an arbitrary generator with "unknown" order.
+        Generator get(get_generator) config(): U2048 = U2048::from(2);  // Use 2 as an arbitrary generator with "unknown" order.
+        State get(get_state) build(|config: &GenesisConfig| genesis_state(config.generator, &config.utxos)): U2048 = U2048::from(2);
         SpentCoins get(get_spent_coins): Vec<(U2048, U2048)>;
-        NewCoins get(get_new_coins): Vec<U2048>
+        NewCoins get(get_new_coins): Vec<U2048>;
+        /// The UTXOs created in the given block, whose primes are cached by the off-chain worker.
+        CreatedUtxos get(created_utxos): (T::BlockNumber, Vec<UTXO>)
     }
+    add_extra_genesis {
+        config(utxos): Vec<UTXO>;
+    }
+}
+
+/// Computes the state of the accumulator after the genesis UTXOs have been added to the generator.
+pub fn genesis_state(generator: U2048, utxos: &[UTXO]) -> U2048 {
+    let (state, _, _) = accumulator::batch_add(generator, &get_utxo_elems(utxos));
+    return state;
+}
+
+/// Creates the membership witness of each genesis UTXO (in the same order as "utxos"). These should
+/// be exported alongside the chain specification so that the genesis coins can be spent.
+pub fn genesis_witnesses(generator: U2048, utxos: &[UTXO]) -> Vec<U2048> {
+    if utxos.is_empty() {
+        return Vec::new();
+    }
+    return witnesses::create_all_mem_wit(generator, &get_utxo_elems(utxos));
+}
+
+/// Returns the off-chain storage key of the prime representation of a SCALE encoded element.
+pub fn prime_cache_key(elem: &[u8]) -> Vec<u8> {
+    let mut key = PRIME_CACHE_PREFIX.to_vec();
+    key.extend_from_slice(elem);
+    return key;
+}
+
+/// Returns the transaction pool tag of a spent element. Every call that deletes an element from the
+/// accumulator provides this tag, so that the pool never includes two deletions of the same element.
+pub fn spend_tag(spent_elem: U2048) -> Vec<u8> {
+    return (b"stateless", spent_elem).encode();
+}
+
+/// Hashes each UTXO to its prime representation.
+fn get_utxo_elems(utxos: &[UTXO]) -> Vec<U2048> {
+    return utxos.iter().map(|utxo| subroutines::hash_to_prime(&utxo.encode())).collect();
+}
+
+/// Executes the accumulator updates of a block given only the state of the accumulator before the
+/// block, which is all that a stateless validator keeps. The calls that fail the checks of this
+/// module are skipped like failed extrinsics. The transition committed in the digest must delete
+/// and add exactly the elements of the remaining calls, except for the swaps whose inclusion also
+/// depends on the balances of the accounts(see "StatelessCall") and the spends of coins that a swap
+/// tried to burn earlier in the block. Returns the state after the block.
+///
+/// NOTE: Since the queue of the module holds at most 100 spends, blocks with more spends are
+/// rejected as the validator cannot tell which of them have been dropped.
+pub fn execute_stateless_block(state: U2048, block: &StatelessBlock) -> rstd::result::Result<U2048, &'static str> {
+    let spends = block.calls.iter().filter(|call| match call {
+        StatelessCall::Spend(..) | StatelessCall::Burn(..) => true,
+        _ => false,
+    }).count();
+    ensure!(spends <= 100, "Too many spends in block.");
+
+    let mut spent = Vec::new();
+    let mut additions = U2048::from(1);
+    // Elements whose inclusion can only be read from the digest.
+    let mut burned = Vec::new();
+    let mut swapped = Vec::new();
+    for call in block.calls.iter() {
+        match call {
+            StatelessCall::Spend(transaction) => {
+                if transaction.input.pub_key == transaction.output.pub_key || transaction.verify_spend(block.number).is_err() {
+                    continue;
+                }
+                let spent_elem = subroutines::hash_to_prime(&transaction.input.encode());
+                let witness = U2048::from_little_endian(&transaction.witness);
+                if spent.contains(&spent_elem) || !witnesses::verify_mem_wit(state, witness, spent_elem) {
+                    continue;
+                }
+                let new_elem = subroutines::hash_to_prime(&transaction.output.encode());
+                // A coin that has been burned earlier in the block can only be spent if the burn failed.
+                if burned.contains(&spent_elem) {
+                    swapped.push(new_elem);
+                    continue;
+                }
+                spent.push(spent_elem);
+                additions *= new_elem;
+            },
+            StatelessCall::Mint(elem) => if *elem >= 2 {
+                additions *= U2048::from(*elem);
+            },
+            StatelessCall::Burn(input, witness) => {
+                let spent_elem = subroutines::hash_to_prime(&input.encode());
+                let witness = U2048::from_little_endian(witness);
+                if !spent.contains(&spent_elem) && witnesses::verify_mem_wit(state, witness, spent_elem) {
+                    burned.push(spent_elem);
+                }
+            },
+            StatelessCall::Swapped(output) => swapped.push(subroutines::hash_to_prime(&output.encode())),
+        }
+    }
+    let deletions = spent.iter().fold(U2048::from(1), |product, elem| product * *elem);
+
+    // The aggregated values of the digest must match the contents of the block.
+    let digest = &block.digest;
+    let deleted = digest.deletion.map(|(_, agg, _)| agg).unwrap_or(U2048::from(1));
+    let added = digest.addition.map(|(_, agg, _)| agg).unwrap_or(U2048::from(1));
+    ensure!(remove_factors(deleted, deletions, &burned) == Some(U2048::from(1)), "Deleted elements do not match the spent coins.");
+    ensure!(remove_factors(added, additions, &swapped) == Some(U2048::from(1)), "Added elements do not match the new coins.");
+    ensure!(digest.verify(state), "Invalid accumulator transition.");
+    return Ok(digest.state);
+}
+
+/// Divides "product" by the required factor and then by each of the optional factors that divides it.
+fn remove_factors(product: U2048, required: U2048, optional: &[U2048]) -> Option<U2048> {
+    if product % required != U2048::zero() {
+        return None;
+    }
+    let mut product = product / required;
+    for factor in optional.iter() {
+        if product % *factor == U2048::zero() {
+            product /= *factor;
+        }
+    }
+    return Some(product);
 }
 
 decl_event!(
@@ -67,52 +409,218 @@ decl_module! {
         /// NOTE: Only works if one transaction per user per block is submitted.
         pub fn addTransaction(origin, transaction: Transaction) -> Result {
             ensure_signed(origin)?;
-            // Arbitrarily cap the number of pending transactions to 100
-            ensure!(SpentCoins::get().len() < 100, "Transaction queue full. Please try again next block.");
-            // Also verify that the user is not spending to themselves
-            ensure!(transaction.input.pub_key != transaction.output.pub_key, "Cannot send coin to yourself.");
+            Self::check_transaction(&transaction)?;
 
-            // Verify witness
             let spent_elem = subroutines::hash_to_prime(&transaction.input.encode());
-            let witness = U2048::from_little_endian(&transaction.witness);
-            ensure!(witnesses::verify_mem_wit(State::get(), witness, spent_elem), "Witness is invalid");
-
             let new_elem = subroutines::hash_to_prime(&transaction.output.encode());
-
-            // Update storage items.
-            SpentCoins::append(&vec![(spent_elem, witness)]);
-
-            Ok(())
+            Self::queue_transaction(transaction, spent_elem, new_elem)
         }
 
         /// Arbitrary replacement for Proof-of-Work to create new coins.
+        /// The coin is added along with the other new coins when the block is finalized. If the state
+        /// was updated right away, the block would change the accumulator without emitting an
+        /// `Addition` event. Observers that only follow the events(e.g. the witness service) could then
+        /// neither create a witness for the minted coin nor update the witnesses of the other coins.
         pub fn mint(origin, elem: u64) -> Result {
             ensure_signed(origin)?;
-            let state = subroutines::mod_exp(Self::get_state(), U2048::from(elem), U2048::from_dec_str(MODULUS).unwrap());
-            State::put(state);
+            // 0 and 1 are not valid accumulator elements and would break the observers that divide
+            // the block products by the added elements(e.g. the indexer).
+            ensure!(elem >= 2, "Minted element must be at least 2.");
+            NewCoins::append(&vec![U2048::from(elem)]);
             Ok(())
         }
 
-        /// Batch delete spent coins and add new coins on block finalization
+        /// Batch delete spent coins and add new coins on block finalization. The resulting state is
+        /// committed in the header digest of every block along with the proofs of the transition.
         fn on_finalize() {
+            let mut state = State::get();
+            let mut digest = AccumulatorDigest { state, deletion: None, addition: None };
+
             // Clause here to protect against empty blocks
             if Self::get_spent_coins().len() > 0 {
                 // Delete spent coins from aggregator and distribute proof
-                let (state, agg, proof) = accumulator::batch_delete(State::get(), &Self::get_spent_coins());
-                Self::deposit_event(Event::Deletion(state, agg, proof));
+                let (new_state, agg, proof) = accumulator::batch_delete(state, &Self::get_spent_coins());
+                Self::deposit_event(Event::Deletion(new_state, agg, proof));
+                digest.deletion = Some((new_state, agg, proof));
+                state = new_state;
+            }
 
+            if Self::get_new_coins().len() > 0 {
                 // Add new coins to aggregator and distribute proof
-                let (state, agg, proof) = accumulator::batch_add(state, &Self::get_new_coins());
-                Self::deposit_event(Event::Addition(state, agg, proof));
-
-                // Update state
-                State::put(state);
+                let (new_state, agg, proof) = accumulator::batch_add(state, &Self::get_new_coins());
+                Self::deposit_event(Event::Addition(new_state, agg, proof));
+                digest.addition = Some((new_state, agg, proof));
+                state = new_state;
             }
 
+            // Update state
+            State::put(state);
+
+            // Commit the new state in the header
+            digest.state = state;
+            <system::Module<T>>::deposit_log(digest.to_digest_item());
+
             // Clear storage
             SpentCoins::kill();
             NewCoins::kill();
         }
+
+        /// Caches the prime representations of the UTXOs created in the block in the off-chain local
+        /// storage, so that the "stateless_hashToPrime" RPC method of the node can return them without
+        /// computing them again. The runtime itself still derives every prime through "hash_to_prime".
+        fn offchain_worker(now: T::BlockNumber) {
+            Self::cache_created_primes(now);
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Performs the checks that do not require the prime representations of the UTXOs.
+    fn check_transaction(transaction: &Transaction) -> Result {
+        // Arbitrarily cap the number of pending transactions to 100
+        ensure!(SpentCoins::get().len() < 100, "Transaction queue full. Please try again next block.");
+        // Also verify that the user is not spending to themselves
+        ensure!(transaction.input.pub_key != transaction.output.pub_key, "Cannot send coin to yourself.");
+        transaction.verify_spend(Self::block_number())
+    }
+
+    /// Returns the number of the current block.
+    fn block_number() -> u64 {
+        return <system::Module<T>>::block_number().saturated_into::<u64>();
+    }
+
+    /// Verifies the witness of the spent element and queues both elements until the block is finalized.
+    fn queue_transaction(transaction: Transaction, spent_elem: U2048, new_elem: U2048) -> Result {
+        // The coin may already have been spent or burned in this block
+        ensure!(!Self::is_spent(spent_elem), "Coin is already spent in this block.");
+
+        // Verify witness
+        let witness = U2048::from_little_endian(&transaction.witness);
+        ensure!(witnesses::verify_mem_wit(State::get(), witness, spent_elem), "Witness is invalid");
+
+        // Update storage items.
+        SpentCoins::append(&vec![(spent_elem, witness)]);
+        NewCoins::append(&vec![new_elem]);
+        Self::record_output(transaction.output);
+
+        Ok(())
+    }
+
+    /// Records a UTXO created in the current block. The list of the previous block is replaced
+    /// rather than cleared on initialization, since the off-chain worker may run after the next
+    /// block has been initialized.
+    fn record_output(output: UTXO) {
+        let now = <system::Module<T>>::block_number();
+        let (number, mut utxos) = Self::created_utxos();
+        if number != now {
+            utxos.clear();
+        }
+        utxos.push(output);
+        <CreatedUtxos<T>>::put((now, utxos));
+    }
+
+    /// Checks that a UTXO can be burned by another module(see the "swap" module) and returns its
+    /// prime representation. Nothing is written to storage.
+    pub fn check_burn(input: &UTXO, witness: U2048) -> rstd::result::Result<U2048, &'static str> {
+        ensure!(SpentCoins::get().len() < 100, "Transaction queue full. Please try again next block.");
+        let spent_elem = subroutines::hash_to_prime(&input.encode());
+        ensure!(!Self::is_spent(spent_elem), "Coin is already spent in this block.");
+        ensure!(witnesses::verify_mem_wit(State::get(), witness, spent_elem), "Witness is invalid");
+        Ok(spent_elem)
+    }
+
+    /// Returns whether an element has already been queued for deletion in this block.
+    fn is_spent(spent_elem: U2048) -> bool {
+        return SpentCoins::get().iter().any(|(elem, _)| *elem == spent_elem);
+    }
+
+    /// Queues a burned element for deletion when the block is finalized.
+    pub fn queue_burn(spent_elem: U2048, witness: U2048) {
+        SpentCoins::append(&vec![(spent_elem, witness)]);
+    }
+
+    /// Queues a new UTXO, given its prime representation, for addition when the block is finalized.
+    pub fn queue_coin(output: UTXO, new_elem: U2048) {
+        NewCoins::append(&vec![new_elem]);
+        Self::record_output(output);
+    }
+
+    /// Validates a call of this module before it enters the transaction pool. Spends must carry a
+    /// valid signature and a witness that is valid against the current state. Since the witness
+    /// becomes stale as soon as the state changes, transactions are only kept in the pool for a
+    /// single block. The spent element is used as the tag so that conflicting spends are detected.
+    pub fn validate_call(call: &Call<T>) -> TransactionValidity {
+        let (transaction, spent_elem) = match call {
+            Call::addTransaction(transaction) => {
+                (transaction, subroutines::hash_to_prime(&transaction.input.encode()))
+            },
+            _ => return Ok(ValidTransaction::default()),
+        };
+
+        if transaction.input.pub_key == transaction.output.pub_key {
+            return Err(InvalidTransaction::Custom(SELF_TRANSFER).into());
+        }
+        match transaction.signer() {
+            Some(spender) => if !transaction.is_unlocked(spender, Self::block_number()) {
+                return Err(InvalidTransaction::Custom(LOCKED).into());
+            },
+            None => return Err(InvalidTransaction::BadProof.into()),
+        }
+        let witness = U2048::from_little_endian(&transaction.witness);
+        if !witnesses::verify_mem_wit(State::get(), witness, spent_elem) {
+            return Err(InvalidTransaction::Stale.into());
+        }
+
+        return Ok(ValidTransaction {
+            priority: 0,
+            requires: Vec::new(),
+            provides: vec![spend_tag(spent_elem)],
+            longevity: 1,
+            propagate: true,
+        });
+    }
+
+    /// Caches the primes of the UTXOs created in the given block(see "offchain_worker").
+    pub fn cache_created_primes(now: T::BlockNumber) {
+        let (number, utxos) = Self::created_utxos();
+        if number != now {
+            return;
+        }
+        for utxo in utxos {
+            let key = prime_cache_key(&utxo.encode());
+            if runtime_io::local_storage_get(StorageKind::PERSISTENT, &key).is_none() {
+                let result = subroutines::hash_to_prime(&utxo.encode());
+                runtime_io::local_storage_set(StorageKind::PERSISTENT, &key, &result.encode());
+            }
+        }
+    }
+
+    /// Returns the modulus of the RSA group.
+    pub fn get_modulus() -> U2048 {
+        return U2048::from_dec_str(MODULUS).unwrap();
+    }
+
+    /// Verify the membership witness of an element against the current state.
+    pub fn verify_witness(elem: U2048, witness: U2048) -> bool {
+        return witnesses::verify_mem_wit(State::get(), witness, elem);
+    }
+}
+
+client::decl_runtime_apis! {
+    /// Runtime API for querying the accumulator without reading raw storage.
+    pub trait StatelessApi {
+        /// Returns the current state of the accumulator.
+        fn get_state() -> U2048;
+        /// Returns the modulus of the RSA group.
+        fn get_modulus() -> U2048;
+        /// Returns the generator the accumulator was instantiated with.
+        fn get_generator() -> U2048;
+        /// Returns the spent elements (along with their witnesses) waiting to be batch deleted.
+        fn get_spent_coins() -> Vec<(U2048, U2048)>;
+        /// Returns the new elements waiting to be batch added.
+        fn get_new_coins() -> Vec<U2048>;
+        /// Verify the membership witness of an element against the current state.
+        fn verify_witness(elem: U2048, witness: U2048) -> bool;
     }
 }
 
@@ -122,9 +630,9 @@ mod tests {
     use super::*;
 
     use runtime_io::with_externalities;
-    use primitives::{H256, Blake2Hasher};
-    use support::{impl_outer_origin, parameter_types};
-    use sr_primitives::{traits::{BlakeTwo256, IdentityLookup, OnFinalize}, testing::Header};
+    use primitives::{H256, Blake2Hasher, sr25519, Pair, offchain::testing::TestOffchainExt};
+    use support::{impl_outer_origin, assert_ok, parameter_types};
+    use sr_primitives::{traits::{BlakeTwo256, IdentityLookup, OnFinalize}, testing::Header, BuildStorage};
     use sr_primitives::weights::Weight;
     use sr_primitives::Perbill;
 
@@ -176,6 +684,39 @@ mod tests {
         system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
     }
 
+    // Builds a genesis storage that additionally accumulates the given UTXOs.
+    fn new_test_ext_with_utxos(utxos: Vec<UTXO>) -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        GenesisConfig {
+            generator: U2048::from(2),
+            utxos,
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    // Derives a key pair along with the public key used as the owner of UTXOs.
+    fn account(seed: u8) -> (sr25519::Pair, H256) {
+        let pair = sr25519::Pair::from_seed(&[seed; 32]);
+        let pub_key = H256::from_slice(pair.public().as_ref());
+        (pair, pub_key)
+    }
+
+    // Creates a transaction signed by "pair" (which should own the input).
+    fn sign_transaction(pair: &sr25519::Pair, input: UTXO, output: UTXO, witness: U2048) -> Transaction {
+        let mut witness_bytes: [u8; 256] = [0; 256];
+        witness.to_little_endian(&mut witness_bytes);
+        let mut transaction = Transaction {
+            input,
+            output,
+            witness: witness_bytes.to_vec(),
+            signature: H512::zero(),
+            preimage: Vec::new(),
+            multisig: None,
+        };
+        transaction.signature = H512::from(pair.sign(&transaction.signing_payload()).0);
+        transaction
+    }
+
     #[test]
     fn test_add() {
         with_externalities(&mut new_test_ext(), || {
@@ -205,21 +746,28 @@ mod tests {
 
     #[test]
     fn test_block() {
+        let (pair_0, key_0) = account(0);
+        let (pair_1, key_1) = account(1);
+        let (pair_2, key_2) = account(2);
+
         with_externalities(&mut new_test_ext(), || {
             // 1. Construct UTXOs.
             let utxo_0 = UTXO {
-                pub_key: H256::from_low_u64_be(0),
+                pub_key: key_0,
                 id: 0,
+                lock: Lock::default(),
             };
 
             let utxo_1 = UTXO {
-                pub_key: H256::from_low_u64_be(1),
+                pub_key: key_1,
                 id: 1,
+                lock: Lock::default(),
             };
 
             let utxo_2 = UTXO {
-                pub_key: H256::from_low_u64_be(2),
+                pub_key: key_2,
                 id: 2,
+                lock: Lock::default(),
             };
 
             // 2. Hash each UTXO to a prime.
@@ -237,48 +785,31 @@ mod tests {
 
             // 5. Construct new UTXOs and derive integer representations.
             let utxo_3 = UTXO {
-                pub_key: H256::from_low_u64_be(1),
+                pub_key: key_1,
                 id: 0,
+                lock: Lock::default(),
             };
 
             let utxo_4 = UTXO {
-                pub_key: H256::from_low_u64_be(2),
+                pub_key: key_2,
                 id: 1,
+                lock: Lock::default(),
             };
 
             let utxo_5 = UTXO {
-                pub_key: H256::from_low_u64_be(0),
+                pub_key: key_0,
                 id: 2,
+                lock: Lock::default(),
             };
 
             let elem_3 = subroutines::hash_to_prime(&utxo_3.encode());
             let elem_4 = subroutines::hash_to_prime(&utxo_4.encode());
             let elem_5 = subroutines::hash_to_prime(&utxo_5.encode());
 
-            // 6. Construct transactions.
-            let mut witness_0: [u8; 256] = [0; 256];
-            witnesses[0].to_little_endian(&mut witness_0);
-            let tx_0 = Transaction {
-                input: utxo_0,
-                output: utxo_3,
-                witness: witness_0.to_vec(),
-            };
-
-            let mut witness_1: [u8; 256] = [0; 256];
-            witnesses[1].to_little_endian(&mut witness_1);
-            let tx_1 = Transaction {
-                input: utxo_1,
-                output: utxo_4,
-                witness: witness_1.to_vec(),
-            };
-
-            let mut witness_2: [u8; 256] = [0; 256];
-            witnesses[2].to_little_endian(&mut witness_2);
-            let tx_2 = Transaction {
-                input: utxo_2,
-                output: utxo_5,
-                witness: witness_2.to_vec(),
-            };
+            // 6. Construct transactions signed by the owners of the inputs.
+            let tx_0 = sign_transaction(&pair_0, utxo_0, utxo_3, witnesses[0]);
+            let tx_1 = sign_transaction(&pair_1, utxo_1, utxo_4, witnesses[1]);
+            let tx_2 = sign_transaction(&pair_2, utxo_2, utxo_5, witnesses[2]);
 
             // 7. Verify transactions. Note that this logic will eventually be executed automatically
             // by the block builder API eventually.
@@ -295,11 +826,398 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_genesis() {
+        let utxos = vec![
+            UTXO { pub_key: H256::from_low_u64_be(0), id: 0, lock: Lock::default() },
+            UTXO { pub_key: H256::from_low_u64_be(1), id: 1, lock: Lock::default() },
+        ];
+        let exported = genesis_witnesses(U2048::from(2), &utxos);
+
+        with_externalities(&mut new_test_ext_with_utxos(utxos.clone()), || {
+            assert_eq!(Stateless::get_generator(), U2048::from(2));
+            assert_eq!(Stateless::get_state(), genesis_state(U2048::from(2), &utxos));
+
+            // Each exported witness must be valid against the genesis state.
+            for (utxo, witness) in utxos.iter().zip(exported.iter()) {
+                let elem = subroutines::hash_to_prime(&utxo.encode());
+                assert_eq!(witnesses::verify_mem_wit(Stateless::get_state(), *witness, elem), true);
+            }
+        });
+    }
+
+    #[test]
+    fn test_pending_coins() {
+        let (pair, key_0) = account(0);
+        let (_, key_1) = account(1);
+        let utxo = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: key_1, id: 0, lock: Lock::default() };
+        let elem = subroutines::hash_to_prime(&utxo.encode());
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            assert_eq!(Stateless::verify_witness(elem, witness), true);
+            assert_eq!(Stateless::verify_witness(elem, U2048::from(3)), false);
+
+            let tx = sign_transaction(&pair, utxo, output, witness);
+
+            // The transaction must be signed by the owner of the input.
+            let (other, _) = account(1);
+            let forged = sign_transaction(&other, utxo, output, witness);
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), forged).is_err(), true);
+
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), tx));
+
+            // Both the spent coin and the new coin should be queued until the block is finalized.
+            assert_eq!(Stateless::get_spent_coins(), vec![(elem, witness)]);
+            assert_eq!(Stateless::get_new_coins(), vec![subroutines::hash_to_prime(&output.encode())]);
+        });
+    }
+
+    #[test]
+    fn test_output_accumulated() {
+        let (pair_0, key_0) = account(0);
+        let (pair_1, key_1) = account(1);
+        let utxo = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: key_1, id: 0, lock: Lock::default() };
+        let next_output = UTXO { pub_key: key_0, id: 1, lock: Lock::default() };
+        let new_elem = subroutines::hash_to_prime(&output.encode());
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), sign_transaction(&pair_0, utxo, output, witness)));
+            Stateless::on_finalize(System::block_number());
+
+            // The output of the transaction is batch added on top of the state after the deletion.
+            let digest = System::digest().logs().iter()
+                .filter_map(AccumulatorDigest::from_digest_item)
+                .last()
+                .unwrap();
+            let (state_after_del, _, _) = digest.deletion.unwrap();
+            let (_, added, _) = digest.addition.unwrap();
+            assert_eq!(added, new_elem);
+
+            // Therefore, the output can be spent in the next block.
+            let new_witness = witnesses::mem_wit_create(state_after_del, added, new_elem).unwrap();
+            assert_eq!(Stateless::verify_witness(new_elem, new_witness), true);
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), sign_transaction(&pair_1, output, next_output, new_witness)));
+        });
+    }
+
     #[test]
     fn test_mint() {
         with_externalities(&mut new_test_ext(), || {
-            Stateless::mint(Origin::signed(1), 3);
+            assert_ok!(Stateless::mint(Origin::signed(1), 3));
+            // The coin is only added when the block is finalized.
+            assert_eq!(Stateless::get_state(), U2048::from(2));
+            Stateless::on_finalize(System::block_number());
             assert_eq!(Stateless::get_state(), U2048::from(8));
+
+            // 0 and 1 cannot be minted.
+            assert_eq!(Stateless::mint(Origin::signed(1), 0).is_err(), true);
+            assert_eq!(Stateless::mint(Origin::signed(1), 1).is_err(), true);
+            assert_eq!(Stateless::get_new_coins().is_empty(), true);
+        });
+    }
+
+    #[test]
+    fn test_mint_addition() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Stateless::mint(Origin::signed(1), 3));
+            assert_ok!(Stateless::mint(Origin::signed(1), 5));
+            Stateless::on_finalize(System::block_number());
+
+            // Blocks that only mint coins still commit the addition and its proof, so the witnesses
+            // of the minted coins can be created from the product of the added elements.
+            let digest = System::digest().logs().iter()
+                .find_map(AccumulatorDigest::from_digest_item)
+                .unwrap();
+            assert_eq!(digest.deletion, None);
+            let (state, added, _) = digest.addition.unwrap();
+            assert_eq!(state, Stateless::get_state());
+            assert_eq!(added, U2048::from(15));
+            assert_eq!(digest.verify(U2048::from(2)), true);
+            let witness = witnesses::mem_wit_create(U2048::from(2), added, U2048::from(5)).unwrap();
+            assert_eq!(Stateless::verify_witness(U2048::from(5), witness), true);
+        });
+    }
+
+    #[test]
+    fn test_offchain_worker() {
+        let (pair_0, key_0) = account(0);
+        let (pair_1, key_1) = account(1);
+        let utxo_0 = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+        let utxo_1 = UTXO { pub_key: key_1, id: 1, lock: Lock::default() };
+        let utxo_2 = UTXO { pub_key: key_0, id: 2, lock: Lock::default() };
+        let cached = |utxo: &UTXO| runtime_io::local_storage_get(StorageKind::PERSISTENT, &prime_cache_key(&utxo.encode()))
+            .and_then(|data| U2048::decode(&mut &data[..]).ok());
+
+        let mut t = new_test_ext_with_utxos(vec![utxo_0]);
+        let (offchain, _state) = TestOffchainExt::new();
+        t.set_offchain_externalities(offchain);
+        with_externalities(&mut t, || {
+            System::set_block_number(1);
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), sign_transaction(&pair_0, utxo_0, utxo_1, U2048::from(2))));
+            Stateless::on_finalize(1);
+            assert_eq!(Stateless::created_utxos(), (1, vec![utxo_1]));
+
+            // Only the UTXOs created in the given block are cached.
+            Stateless::cache_created_primes(2);
+            assert_eq!(cached(&utxo_1), None);
+            Stateless::cache_created_primes(1);
+            assert_eq!(cached(&utxo_1), Some(subroutines::hash_to_prime(&utxo_1.encode())));
+
+            // Spending the UTXO in the next block replaces the list of created UTXOs.
+            System::set_block_number(2);
+            let tx = sign_transaction(&pair_1, utxo_1, utxo_2, U2048::from(2));
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), tx));
+            assert_eq!(Stateless::created_utxos(), (2, vec![utxo_2]));
+        });
+    }
+
+    #[test]
+    fn test_stateless_block() {
+        let modulus = U2048::from_dec_str(MODULUS).unwrap();
+        let (pair_0, key_0) = account(0);
+        let (pair_1, key_1) = account(1);
+        let (_, key_2) = account(2);
+        let (_, key_3) = account(3);
+        let utxo_0 = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+        let utxo_1 = UTXO { pub_key: key_1, id: 1, lock: Lock::default() };
+        let utxo_2 = UTXO { pub_key: key_2, id: 2, lock: Lock::default() };
+        let utxo_3 = UTXO { pub_key: key_3, id: 3, lock: Lock::default() };
+        let elem_1 = subroutines::hash_to_prime(&utxo_1.encode());
+        let elem_2 = subroutines::hash_to_prime(&utxo_2.encode());
+        let genesis = genesis_state(U2048::from(2), &[utxo_0, utxo_1]);
+
+        // 1. Produce a chain of blocks on a regular node.
+        let mut blocks = Vec::new();
+        let mut full_state = U2048::from(0);
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo_0, utxo_1]), || {
+            let chain = vec![
+                // Spend utxo_0 (witness: the generator raised to the other element) and mint 7.
+                (vec![sign_transaction(&pair_0, utxo_0, utxo_2, subroutines::mod_exp(U2048::from(2), elem_1, modulus))], vec![7]),
+                // Spend utxo_1 after utxo_0 has been spent and utxo_2 and 7 have been added.
+                (vec![sign_transaction(&pair_1, utxo_1, utxo_3, subroutines::mod_exp(U2048::from(2), elem_2 * U2048::from(7), modulus))], vec![]),
+            ];
+
+            for (transactions, mints) in chain {
+                let mut calls = Vec::new();
+                for transaction in transactions {
+                    assert_ok!(Stateless::addTransaction(Origin::signed(1), transaction.clone()));
+                    calls.push(StatelessCall::Spend(transaction));
+                }
+                for elem in mints {
+                    assert_ok!(Stateless::mint(Origin::signed(1), elem));
+                    calls.push(StatelessCall::Mint(elem));
+                }
+                // A spend that fails on-chain is skipped.
+                let mut failed = sign_transaction(&pair_0, utxo_0, utxo_3, U2048::from(1));
+                assert_eq!(Stateless::addTransaction(Origin::signed(1), failed.clone()).is_err(), true);
+                calls.push(StatelessCall::Spend(failed.clone()));
+                failed.signature = H512::zero();
+                calls.push(StatelessCall::Spend(failed));
+                Stateless::on_finalize(System::block_number());
+
+                let digest = System::digest().logs().iter()
+                    .filter_map(AccumulatorDigest::from_digest_item)
+                    .last()
+                    .unwrap();
+                blocks.push(StatelessBlock { number: System::block_number(), calls, digest });
+            }
+            full_state = Stateless::get_state();
+        });
+
+        // 2. Execute the chain given only the state of the accumulator.
+        // Blocks that do not match the committed transition are rejected.
+        let mut forged = blocks[0].clone();
+        forged.digest.state = forged.digest.state + U2048::from(1);
+        assert_eq!(execute_stateless_block(genesis, &forged).is_err(), true);
+        let mut forged = blocks[0].clone();
+        forged.calls.push(StatelessCall::Mint(11));
+        assert_eq!(execute_stateless_block(genesis, &forged).is_err(), true);
+        let mut forged = blocks[0].clone();
+        forged.calls.remove(0);
+        assert_eq!(execute_stateless_block(genesis, &forged).is_err(), true);
+
+        // The swaps that are not included in the digest are skipped as well.
+        let mut witness = [0u8; 256];
+        subroutines::mod_exp(U2048::from(2), subroutines::hash_to_prime(&utxo_0.encode()), modulus).to_little_endian(&mut witness);
+        let mut block = blocks[0].clone();
+        block.calls.push(StatelessCall::Swapped(utxo_3));
+        block.calls.push(StatelessCall::Burn(utxo_1, witness.to_vec()));
+        let state = execute_stateless_block(genesis, &block).unwrap();
+        assert_eq!(execute_stateless_block(genesis, &blocks[0]), Ok(state));
+        assert_eq!(execute_stateless_block(state, &blocks[1]), Ok(full_state));
+
+        // So are the spends of coins that have already been spent in the block.
+        let mut block = blocks[0].clone();
+        let spend = block.calls[0].clone();
+        block.calls.push(spend);
+        assert_eq!(execute_stateless_block(genesis, &block), Ok(state));
+
+        // So are the mints of 0 and 1, which fail on-chain.
+        let mut block = blocks[0].clone();
+        block.calls.push(StatelessCall::Mint(0));
+        block.calls.push(StatelessCall::Mint(1));
+        assert_eq!(execute_stateless_block(genesis, &block), Ok(state));
+    }
+
+    #[test]
+    fn test_validate_call() {
+        let (pair, key_0) = account(0);
+        let (other, key_1) = account(1);
+        let utxo = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: key_1, id: 0, lock: Lock::default() };
+        let elem = subroutines::hash_to_prime(&utxo.encode());
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            let tx = sign_transaction(&pair, utxo, output, witness);
+            let validity = Stateless::validate_call(&Call::addTransaction(tx)).unwrap();
+            assert_eq!(validity.provides, vec![(b"stateless", elem).encode()]);
+            assert_eq!(validity.longevity, 1);
+
+            let forged = sign_transaction(&other, utxo, output, witness);
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(forged)), Err(InvalidTransaction::BadProof.into()));
+
+            let stale = sign_transaction(&pair, utxo, output, witness + U2048::from(1));
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(stale)), Err(InvalidTransaction::Stale.into()));
+
+            let to_self = sign_transaction(&pair, utxo, UTXO { pub_key: key_0, id: 1, lock: Lock::default() }, witness);
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(to_self)),
+                       Err(InvalidTransaction::Custom(SELF_TRANSFER).into()));
+        });
+    }
+
+    #[test]
+    fn test_htlc() {
+        let (alice, key_0) = account(0);
+        let (bob, key_1) = account(1);
+        let (_, key_2) = account(2);
+        let secret = b"secret".to_vec();
+
+        // 1. Bob can spend the UTXO once he reveals the preimage.
+        let lock = Lock {
+            timelock: 0,
+            hashlock: Some(H256::from(runtime_io::blake2_256(&secret))),
+            refund: Some((key_0, 10)),
+        };
+        let utxo = UTXO { pub_key: key_1, id: 0, lock };
+        let output = UTXO { pub_key: key_2, id: 0, lock: Lock::default() };
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            System::set_block_number(1);
+            let mut tx = sign_transaction(&bob, utxo, output, witness);
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(tx.clone())), Err(InvalidTransaction::Custom(LOCKED).into()));
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), tx.clone()).is_err(), true);
+            tx.preimage = b"guess".to_vec();
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), tx.clone()).is_err(), true);
+
+            // Alice cannot be refunded before the refund height.
+            let refund = sign_transaction(&alice, utxo, output, witness);
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), refund).is_err(), true);
+
+            tx.preimage = secret.clone();
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), tx));
+        });
+
+        // 2. Otherwise, Alice can spend the UTXO once the refund height is reached.
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            System::set_block_number(10);
+            let refund = sign_transaction(&alice, utxo, output, witness);
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(refund.clone())).is_ok(), true);
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), refund));
+        });
+
+        // 3. Timelocked UTXOs cannot be spent before the given block number.
+        let utxo = UTXO { pub_key: key_1, id: 0, lock: Lock { timelock: 5, hashlock: None, refund: None } };
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            System::set_block_number(4);
+            let tx = sign_transaction(&bob, utxo, output, witness);
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), tx.clone()).is_err(), true);
+            System::set_block_number(5);
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), tx));
+        });
+    }
+
+    #[test]
+    fn test_multisig() {
+        let pairs: Vec<sr25519::Pair> = (0..3).map(|seed| account(seed).0).collect();
+        let keys: Vec<H256> = (0..3).map(|seed| account(seed).1).collect();
+        let (_, key_3) = account(3);
+        let policy = MultisigPolicy { threshold: 2, keys };
+        let utxo = UTXO { pub_key: policy.hash(), id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: key_3, id: 0, lock: Lock::default() };
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        // Creates a transaction signed by the keys at the given indices of the policy.
+        let sign = |policy: &MultisigPolicy, indices: &[u32]| {
+            let mut tx = sign_transaction(&pairs[0], utxo, output, witness);
+            tx.signature = H512::zero();
+            let signatures = indices.iter()
+                .map(|index| (*index, H512::from(pairs[*index as usize].sign(&tx.signing_payload()).0)))
+                .collect();
+            tx.multisig = Some(MultisigSpend { policy: policy.clone(), signatures });
+            tx
+        };
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            // Not enough signatures or repeated signatures are rejected.
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), sign(&policy, &[1])).is_err(), true);
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), sign(&policy, &[1, 1])).is_err(), true);
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), sign(&policy, &[2, 0])).is_err(), true);
+
+            // The policy must match the one the UTXO is locked to.
+            let weaker = MultisigPolicy { threshold: 1, keys: policy.keys.clone() };
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), sign(&weaker, &[1])).is_err(), true);
+
+            // A single signature of a co-owner is not valid either.
+            let tx = sign_transaction(&pairs[0], utxo, output, witness);
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(tx)), Err(InvalidTransaction::BadProof.into()));
+
+            let tx = sign(&policy, &[0, 2]);
+            assert_eq!(Stateless::validate_call(&Call::addTransaction(tx.clone())).is_ok(), true);
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), tx));
+        });
+    }
+
+    #[test]
+    fn test_digest() {
+        let (pair, key_0) = account(0);
+        let (_, key_1) = account(1);
+        let utxo = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: key_1, id: 0, lock: Lock::default() };
+        let witness = genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            let prev_state = Stateless::get_state();
+
+            // Blocks that do not modify the accumulator still commit to the state.
+            Stateless::on_finalize(System::block_number());
+            let digest = System::digest().logs().iter()
+                .find_map(AccumulatorDigest::from_digest_item)
+                .unwrap();
+            assert_eq!(digest, AccumulatorDigest { state: prev_state, deletion: None, addition: None });
+            assert_eq!(digest.verify(prev_state), true);
+
+            let tx = sign_transaction(&pair, utxo, output, witness);
+            assert_ok!(Stateless::addTransaction(Origin::signed(1), tx));
+            Stateless::on_finalize(System::block_number());
+
+            let digest = System::digest().logs().iter()
+                .filter_map(AccumulatorDigest::from_digest_item)
+                .last()
+                .unwrap();
+            assert_eq!(digest.state, Stateless::get_state());
+            assert_eq!(digest.deletion.is_some() && digest.addition.is_some(), true);
+            assert_eq!(digest.verify(prev_state), true);
+
+            // A digest committing to a different state must be rejected.
+            let forged = AccumulatorDigest { state: digest.state + U2048::from(1), ..digest.clone() };
+            assert_eq!(forged.verify(prev_state), false);
         });
     }
 
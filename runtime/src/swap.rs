@@ -0,0 +1,430 @@
+/// Atomic swaps between the UTXOs of the "stateless" module and the balances of the account-based
+/// "StatelessAccounts" module. Both sides of a swap are checked before anything is written to
+/// storage. The swap then queues the deletion and addition in each accumulator, so the updates of
+/// both modules are applied by the same block finalization and covered by the proofs emitted there.
+///
+/// NOTE: Since UTXOs are not explicitly value bearing, every UTXO is worth "UtxoValue" tokens.
+
+use support::{decl_module, decl_event, ensure, dispatch::Result, traits::Get};
+use system::ensure_signed;
+use primitive_types::{H256, H512};
+use rstd::prelude::Vec;
+use sr_primitives::transaction_validity::{TransactionValidity, ValidTransaction, InvalidTransaction};
+use codec::{Encode, Decode};
+use accumulator::*;
+use vector_commitment::{Account, Balance, Key, Opening};
+use crate::stateless::{self, UTXO, Lock};
+
+/// Burns a UTXO and credits its value to a key of the vector commitment.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Default, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct UtxoToAccount {
+    pub input: UTXO,
+    pub witness: Vec<u8>,
+    /// sr25519 signature of the owner of the input over the "signing_payload".
+    pub signature: H512,
+    pub key: Key,
+    /// The value currently committed at the key.
+    pub account: Account,
+    pub opening: Opening,
+}
+
+impl UtxoToAccount {
+    /// Returns the payload that must be signed by the owner of the input.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        return (self.input, self.key).encode();
+    }
+
+    /// Verifies that the swap has been signed by the owner of the input.
+    pub fn verify_signature(&self) -> bool {
+        return stateless::verify_signature(&self.signature, &self.input.pub_key, &self.signing_payload());
+    }
+}
+
+/// Debits the value of a UTXO from a key and creates a new UTXO.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Default, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct AccountToUtxo {
+    pub key: Key,
+    /// The public key that the key is derived from(see "account_key").
+    pub pub_key: H256,
+    /// The value currently committed at the key, where the nonce is the nonce of this debit(see
+    /// "vector_commitment::Transaction").
+    pub account: Account,
+    pub opening: Opening,
+    pub output: UTXO,
+    /// sr25519 signature of "pub_key" over the "signing_payload".
+    pub signature: H512,
+}
+
+impl AccountToUtxo {
+    /// Returns the payload that must be signed by the owner of the key. It includes the nonce of
+    /// the key, which makes the signature valid for a single debit only.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        return (self.key, self.account.nonce, self.output).encode();
+    }
+
+    /// Verifies that the swap has been signed by the owner of the key.
+    pub fn verify_signature(&self) -> bool {
+        return vector_commitment::verify_signature(&self.signature, &self.pub_key, &self.signing_payload());
+    }
+}
+
+pub trait Trait: stateless::Trait + vector_commitment::Trait {
+    type Event: From<Event> + Into<<Self as system::Trait>::Event>;
+    /// The number of tokens a UTXO is worth.
+    type UtxoValue: Get<Balance>;
+}
+
+decl_event!(
+    pub enum Event {
+        SwappedToAccount(U2048, Key, Balance),
+        SwappedToUtxo(Key, Balance, U2048),
+    }
+);
+
+decl_module! {
+    /// The module declaration.
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event() = default;
+        const UtxoValue: Balance = T::UtxoValue::get();
+
+        /// Burns a UTXO and credits its value to a key.
+        pub fn swap_to_account(origin, swap: UtxoToAccount) -> Result {
+            ensure_signed(origin)?;
+            ensure!(swap.verify_signature(), "Signature is invalid.");
+            ensure!(swap.input.lock == Lock::default(), "Locked coins cannot be swapped.");
+
+            // Check both sides before updating storage
+            let witness = U2048::from_little_endian(&swap.witness);
+            let spent_elem = <stateless::Module<T>>::check_burn(&swap.input, witness)?;
+            let (committed, pending) = <vector_commitment::Module<T>>::load(swap.key, swap.account, swap.opening)?;
+            let new_balance = pending.balance.checked_add(T::UtxoValue::get()).ok_or("Balance is too large.")?;
+
+            let new_account = Account { balance: new_balance, ..pending };
+            <vector_commitment::Module<T>>::store(&[(swap.key, committed, new_account, swap.opening)])?;
+            <stateless::Module<T>>::queue_burn(spent_elem, witness);
+            Self::deposit_event(Event::SwappedToAccount(spent_elem, swap.key, new_balance));
+            Ok(())
+        }
+
+        /// Debits the value of a UTXO from a key and creates the given UTXO. Like the transfers of the
+        /// account-based module, debits are signed by the owner of the key and increment its nonce.
+        pub fn swap_to_utxo(origin, swap: AccountToUtxo) -> Result {
+            ensure_signed(origin)?;
+            ensure!(<vector_commitment::Module<T>>::account_key(&swap.pub_key) == Some(swap.key), "Key does not belong to the signer.");
+            ensure!(swap.verify_signature(), "Signature is invalid.");
+            let (committed, pending) = <vector_commitment::Module<T>>::load(swap.key, swap.account, swap.opening)?;
+            ensure!(swap.account.nonce == pending.nonce, "Nonce is invalid.");
+            let new_balance = pending.balance.checked_sub(T::UtxoValue::get()).ok_or("Balance is too low.")?;
+            let nonce = pending.nonce.checked_add(1).ok_or("Nonce is too large.")?;
+
+            let new_elem = subroutines::hash_to_prime(&swap.output.encode());
+            let new_account = Account { balance: new_balance, nonce };
+            <vector_commitment::Module<T>>::store(&[(swap.key, committed, new_account, swap.opening)])?;
+            <stateless::Module<T>>::queue_coin(swap.output, new_elem);
+            Self::deposit_event(Event::SwappedToUtxo(swap.key, new_balance, new_elem));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Validates a call of this module before it enters the transaction pool. Burns provide the same
+    /// tag as the spends of the "stateless" module, so that the pool does not include both a spend
+    /// and a burn of the same UTXO. Like spends, burns are only kept in the pool for a single block.
+    pub fn validate_call(call: &Call<T>) -> TransactionValidity {
+        let swap = match call {
+            Call::swap_to_account(swap) => swap,
+            _ => return Ok(ValidTransaction::default()),
+        };
+
+        if !swap.verify_signature() {
+            return Err(InvalidTransaction::BadProof.into());
+        }
+        let spent_elem = subroutines::hash_to_prime(&swap.input.encode());
+        if !<stateless::Module<T>>::verify_witness(spent_elem, U2048::from_little_endian(&swap.witness)) {
+            return Err(InvalidTransaction::Stale.into());
+        }
+
+        return Ok(ValidTransaction {
+            priority: 0,
+            requires: Vec::new(),
+            provides: vec![stateless::spend_tag(spent_elem)],
+            longevity: 1,
+            propagate: true,
+        });
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use runtime_io::with_externalities;
+    use primitives::{H256, Blake2Hasher, sr25519, Pair};
+    use support::{impl_outer_origin, assert_ok, parameter_types};
+    use sr_primitives::{traits::{BlakeTwo256, IdentityLookup, OnFinalize}, testing::Header, BuildStorage};
+    use sr_primitives::weights::Weight;
+    use sr_primitives::Perbill;
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    parameter_types! {
+        pub const BlockHashCount: u64 = 250;
+        pub const MaximumBlockWeight: Weight = 1024;
+        pub const MaximumBlockLength: u32 = 2 * 1024;
+        pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+        pub const KeySpace: Key = Key::max_value();
+        pub const CheckpointWindow: u64 = 8;
+        pub const UtxoValue: Balance = 1;
+    }
+
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Call = ();
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type WeightMultiplierUpdate = ();
+        type Event = ();
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+    }
+
+    impl stateless::Trait for Test {
+        type Event = ();
+    }
+
+    impl vector_commitment::Trait for Test {
+        type Event = ();
+        type KeySpace = KeySpace;
+        type CheckpointWindow = CheckpointWindow;
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type UtxoValue = UtxoValue;
+    }
+
+    type Swap = Module<Test>;
+    type Stateless = stateless::Module<Test>;
+    type StatelessAccounts = vector_commitment::Module<Test>;
+    type System = system::Module<Test>;
+
+    fn new_test_ext_with_utxos(utxos: Vec<UTXO>) -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        stateless::GenesisConfig {
+            generator: U2048::from(2),
+            utxos,
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn finalize() {
+        Stateless::on_finalize(System::block_number());
+        StatelessAccounts::on_finalize(System::block_number());
+        System::set_block_number(System::block_number() + 1);
+    }
+
+    #[test]
+    fn test_swap() {
+        let modulus = U2048::from_dec_str(MODULUS).unwrap();
+        let pair = sr25519::Pair::from_seed(&[0; 32]);
+        let utxo = UTXO { pub_key: H256::from_slice(pair.public().as_ref()), id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: H256::from_slice(pair.public().as_ref()), id: 1, lock: Lock::default() };
+        let witness = stateless::genesis_witnesses(U2048::from(2), &[utxo])[0];
+        let pub_key = H256::from_slice(pair.public().as_ref());
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            let key = StatelessAccounts::account_key(&pub_key).unwrap();
+
+            // 1. Burn the UTXO and credit an empty key.
+            let generator = StatelessAccounts::get_state();
+            let opening = StatelessAccounts::open_empty(key, U2048::from(1)).unwrap();
+            let mut witness_bytes: [u8; 256] = [0; 256];
+            witness.to_little_endian(&mut witness_bytes);
+            let mut swap = UtxoToAccount {
+                input: utxo,
+                witness: witness_bytes.to_vec(),
+                signature: H512::zero(),
+                key,
+                account: Account::default(),
+                opening,
+            };
+            assert_eq!(Swap::swap_to_account(Origin::signed(1), swap.clone()).is_err(), true);
+            swap.signature = H512::from(pair.sign(&swap.signing_payload()).0);
+
+            // Neither side is updated if the other one is invalid or if the key is outside of the key space.
+            let mut outside = swap.clone();
+            outside.key = Key::max_value();
+            outside.opening = StatelessAccounts::open_empty(outside.key, U2048::from(1)).unwrap();
+            outside.signature = H512::from(pair.sign(&outside.signing_payload()).0);
+            assert_eq!(Swap::swap_to_account(Origin::signed(1), outside).is_err(), true);
+            let mut invalid = swap.clone();
+            invalid.opening = Opening { pi_i: swap.opening.pi_e, pi_e: swap.opening.pi_i, ..swap.opening };
+            assert_eq!(Swap::swap_to_account(Origin::signed(1), invalid).is_err(), true);
+            assert_eq!(Stateless::get_spent_coins().len(), 0);
+
+            assert_ok!(Swap::swap_to_account(Origin::signed(1), swap.clone()));
+            assert_eq!(Swap::swap_to_account(Origin::signed(1), swap).is_err(), true);
+            let block = System::block_number();
+            finalize();
+
+            // The UTXO has been deleted and the key holds the value of the UTXO.
+            let account = Account { balance: 1, nonce: 0 };
+            let elem = StatelessAccounts::write_elem(key, account, block).unwrap();
+            assert_eq!(Stateless::get_state(), U2048::from(2));
+            assert_eq!(StatelessAccounts::get_state(), subroutines::mod_exp(generator, elem, modulus));
+
+            // 2. Debit the key and create a new UTXO. The debit must be signed by the owner of the key.
+            let mut debit = AccountToUtxo {
+                key,
+                pub_key,
+                account,
+                opening: StatelessAccounts::open(key, account, block, elem).unwrap(),
+                output,
+                signature: H512::zero(),
+            };
+            assert_eq!(Swap::swap_to_utxo(Origin::signed(1), debit.clone()).is_err(), true);
+            debit.signature = H512::from(pair.sign(&debit.signing_payload()).0);
+            assert_ok!(Swap::swap_to_utxo(Origin::signed(1), debit.clone()));
+            assert_eq!(Swap::swap_to_utxo(Origin::signed(1), debit.clone()).is_err(), true);
+
+            // A second debit in the same block uses the next nonce, but the balance has been spent.
+            debit.account.nonce = 1;
+            debit.signature = H512::from(pair.sign(&debit.signing_payload()).0);
+            assert_eq!(Swap::swap_to_utxo(Origin::signed(1), debit).is_err(), true);
+            let block = System::block_number();
+            finalize();
+
+            // The key is empty apart from its incremented nonce.
+            let new_elem = subroutines::hash_to_prime(&output.encode());
+            let nonce_elem = StatelessAccounts::write_elem(key, Account { balance: 0, nonce: 1 }, block).unwrap();
+            assert_eq!(Stateless::get_state(), subroutines::mod_exp(U2048::from(2), new_elem, modulus));
+            assert_eq!(StatelessAccounts::get_state(), subroutines::mod_exp(generator, nonce_elem, modulus));
+        });
+    }
+
+    #[test]
+    fn test_swap_theft() {
+        let owner = sr25519::Pair::from_seed(&[0; 32]);
+        let thief = sr25519::Pair::from_seed(&[1; 32]);
+        let owner_key = H256::from_slice(owner.public().as_ref());
+        let thief_key = H256::from_slice(thief.public().as_ref());
+        let utxo = UTXO { pub_key: owner_key, id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: thief_key, id: 1, lock: Lock::default() };
+        let witness = stateless::genesis_witnesses(U2048::from(2), &[utxo])[0];
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            // Fund the key of the owner.
+            let key = StatelessAccounts::account_key(&owner_key).unwrap();
+            let mut witness_bytes: [u8; 256] = [0; 256];
+            witness.to_little_endian(&mut witness_bytes);
+            let mut swap = UtxoToAccount {
+                input: utxo,
+                witness: witness_bytes.to_vec(),
+                signature: H512::zero(),
+                key,
+                account: Account::default(),
+                opening: StatelessAccounts::open_empty(key, U2048::from(1)).unwrap(),
+            };
+            swap.signature = H512::from(owner.sign(&swap.signing_payload()).0);
+            assert_ok!(Swap::swap_to_account(Origin::signed(1), swap));
+            let block = System::block_number();
+            finalize();
+
+            let account = Account { balance: 1, nonce: 0 };
+            let elem = StatelessAccounts::write_elem(key, account, block).unwrap();
+            let state = StatelessAccounts::get_state();
+            let mut debit = AccountToUtxo {
+                key,
+                pub_key: owner_key,
+                account,
+                opening: StatelessAccounts::open(key, account, block, elem).unwrap(),
+                output,
+                signature: H512::zero(),
+            };
+
+            // The thief cannot sign for the key of the owner.
+            debit.signature = H512::from(thief.sign(&debit.signing_payload()).0);
+            assert_eq!(Swap::swap_to_utxo(Origin::signed(2), debit.clone()), Err("Signature is invalid."));
+
+            // Nor claim the key with their own public key.
+            debit.pub_key = thief_key;
+            assert_eq!(Swap::swap_to_utxo(Origin::signed(2), debit.clone()), Err("Key does not belong to the signer."));
+
+            // A debit signed by the owner cannot be redirected to another output.
+            debit.pub_key = owner_key;
+            debit.output = UTXO { pub_key: owner_key, id: 2, lock: Lock::default() };
+            debit.signature = H512::from(owner.sign(&debit.signing_payload()).0);
+            debit.output = output;
+            assert_eq!(Swap::swap_to_utxo(Origin::signed(2), debit), Err("Signature is invalid."));
+
+            // Neither module has been updated.
+            finalize();
+            assert_eq!(StatelessAccounts::get_state(), state);
+            assert_eq!(Stateless::get_state(), U2048::from(2));
+        });
+    }
+
+    #[test]
+    fn test_burn_then_spend() {
+        let pair = sr25519::Pair::from_seed(&[0; 32]);
+        let pub_key = H256::from_slice(pair.public().as_ref());
+        let utxo = UTXO { pub_key, id: 0, lock: Lock::default() };
+        let output = UTXO { pub_key: H256::from_low_u64_be(1), id: 1, lock: Lock::default() };
+        let witness = stateless::genesis_witnesses(U2048::from(2), &[utxo])[0];
+        let mut witness_bytes: [u8; 256] = [0; 256];
+        witness.to_little_endian(&mut witness_bytes);
+
+        with_externalities(&mut new_test_ext_with_utxos(vec![utxo]), || {
+            let key = StatelessAccounts::account_key(&pub_key).unwrap();
+            let mut swap = UtxoToAccount {
+                input: utxo,
+                witness: witness_bytes.to_vec(),
+                signature: H512::zero(),
+                key,
+                account: Account::default(),
+                opening: StatelessAccounts::open_empty(key, U2048::from(1)).unwrap(),
+            };
+            swap.signature = H512::from(pair.sign(&swap.signing_payload()).0);
+            let mut spend = stateless::Transaction {
+                input: utxo,
+                output,
+                witness: witness_bytes.to_vec(),
+                signature: H512::zero(),
+                preimage: Vec::new(),
+                multisig: None,
+            };
+            spend.signature = H512::from(pair.sign(&spend.signing_payload()).0);
+
+            // The pool treats the burn and the spend as conflicting deletions of the same element.
+            let burn_validity = Swap::validate_call(&Call::swap_to_account(swap.clone())).unwrap();
+            let spend_validity = Stateless::validate_call(&stateless::Call::addTransaction(spend.clone())).unwrap();
+            assert_eq!(burn_validity.provides, spend_validity.provides);
+
+            // Once the UTXO has been burned, it cannot be spent in the same block.
+            assert_ok!(Swap::swap_to_account(Origin::signed(1), swap));
+            assert_eq!(Stateless::addTransaction(Origin::signed(1), spend), Err("Coin is already spent in this block."));
+            assert_eq!(Stateless::get_spent_coins().len(), 1);
+            assert_eq!(Stateless::get_new_coins().len(), 0);
+
+            // The block can still be finalized.
+            finalize();
+            assert_eq!(Stateless::get_state(), U2048::from(2));
+        });
+    }
+}
@@ -0,0 +1,113 @@
+//! Block import wrapper that verifies the accumulator transition committed in the header digest of
+//! every imported block. This allows light clients to check the accumulator updates using the
+//! proofs of exponentiation without executing the blocks. Full nodes additionally check the
+//! transition against the executed state of the parent block. The proofs emitted by the
+//! `Deletion`/`Addition` events are not audited separately: they are the same values that the
+//! runtime deposits in the digest, and a block whose digest differs from the one deposited during
+//! execution fails to import.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use codec::Decode;
+use consensus_common::{BlockImport, BlockImportParams, ImportResult, Error as ConsensusError, well_known_cache_keys::Id as CacheKeyId};
+use primitives::{twox_128, storage::StorageKey, Blake2Hasher, H256};
+use sr_primitives::{generic::BlockId, traits::Header as HeaderT};
+use substrate_client::{Client, CallExecutor, backend::Backend};
+use stateless_blockchain_runtime::{opaque::{Block, Header}, stateless::AccumulatorDigest};
+use accumulator::U2048;
+
+/// Extracts the accumulator digest of a header.
+pub fn accumulator_digest(header: &Header) -> Option<AccumulatorDigest> {
+	header.digest().logs().iter().find_map(AccumulatorDigest::from_digest_item)
+}
+
+/// Wraps an inner block import and rejects blocks with a missing or invalid accumulator transition.
+pub struct AccumulatorBlockImport<I, B, E, RA> {
+	inner: I,
+	client: Arc<Client<B, E, Block, RA>>,
+	/// Whether the executed state of the parent block is available locally.
+	full: bool,
+}
+
+impl<I: Clone, B, E, RA> Clone for AccumulatorBlockImport<I, B, E, RA> {
+	fn clone(&self) -> Self {
+		AccumulatorBlockImport {
+			inner: self.inner.clone(),
+			client: self.client.clone(),
+			full: self.full,
+		}
+	}
+}
+
+impl<I, B, E, RA> AccumulatorBlockImport<I, B, E, RA> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	/// Creates a wrapper for full nodes, which compare the transition against the executed state.
+	/// Since the runtime deposits the same digest when executing the block, a digest that does not
+	/// match the executed state of the block itself is rejected by `Executive::final_checks`.
+	pub fn new_full(inner: I, client: Arc<Client<B, E, Block, RA>>) -> Self {
+		AccumulatorBlockImport { inner, client, full: true }
+	}
+
+	/// Creates a wrapper for light clients, which follow the states committed in the headers.
+	pub fn new_light(inner: I, client: Arc<Client<B, E, Block, RA>>) -> Self {
+		AccumulatorBlockImport { inner, client, full: false }
+	}
+
+	/// Returns the state of the accumulator after the given block.
+	fn state_at(&self, hash: H256) -> Result<U2048, ConsensusError> {
+		if self.full {
+			return self.stored_state(BlockId::Hash(hash));
+		}
+
+		let header = self.client.header(&BlockId::Hash(hash))
+			.map_err(|e| ConsensusError::ClientImport(format!("{:?}", e)))?
+			.ok_or_else(|| ConsensusError::ClientImport(format!("Unknown block {:?}", hash)))?;
+		if header.number() == &0 {
+			// Light clients keep the genesis storage locally.
+			return self.stored_state(BlockId::Number(0));
+		}
+		accumulator_digest(&header)
+			.map(|digest| digest.state)
+			.ok_or_else(|| ConsensusError::ClientImport(format!("Block {:?} has no accumulator digest", hash)))
+	}
+
+	/// Reads the state of the accumulator from storage.
+	fn stored_state(&self, id: BlockId<Block>) -> Result<U2048, ConsensusError> {
+		let key = StorageKey(twox_128(b"Stateless State").to_vec());
+		self.client.storage(&id, &key)
+			.map_err(|e| ConsensusError::ClientImport(format!("{:?}", e)))?
+			.and_then(|data| Decode::decode(&mut &data.0[..]).ok())
+			.ok_or_else(|| ConsensusError::ClientImport(format!("State of the accumulator is unavailable at {:?}", id)))
+	}
+}
+
+impl<I, B, E, RA> BlockImport<Block> for AccumulatorBlockImport<I, B, E, RA> where
+	I: BlockImport<Block, Error = ConsensusError>,
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	type Error = ConsensusError;
+
+	fn check_block(&mut self, hash: H256, parent_hash: H256) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(hash, parent_hash)
+	}
+
+	fn import_block(
+		&mut self,
+		block: BlockImportParams<Block>,
+		new_cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let hash = block.header.hash();
+		let digest = accumulator_digest(&block.header)
+			.ok_or_else(|| ConsensusError::ClientImport(format!("Block {:?} has no accumulator digest", hash)))?;
+		let parent_hash = *block.header.parent_hash();
+		let prev_state = self.state_at(parent_hash)?;
+		if !digest.verify(prev_state) {
+			return Err(ConsensusError::ClientImport(format!("Invalid accumulator transition in block {:?}", hash)));
+		}
+
+		self.inner.import_block(block, new_cache)
+	}
+}
@@ -1,11 +1,14 @@
-use primitives::{Pair, Public};
+use primitives::{Pair, Public, H256};
 use stateless_blockchain_runtime::{
 	AccountId, BabeConfig, BalancesConfig, GenesisConfig, GrandpaConfig,
-	SudoConfig, IndicesConfig, SystemConfig, WASM_BINARY, 
+	SudoConfig, IndicesConfig, SystemConfig, StatelessConfig, WASM_BINARY,
 };
+use stateless_blockchain_runtime::stateless::{self, UTXO};
 use babe_primitives::{AuthorityId as BabeId};
 use grandpa_primitives::{AuthorityId as GrandpaId};
-use substrate_service;
+use substrate_service::{self, Properties};
+use accumulator::U2048;
+use serde_json::json;
 
 // Note this is the URL for the telemetry server
 //const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
@@ -31,6 +34,25 @@ pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Pu
 		.public()
 }
 
+/// Helper function to generate a UTXO owned by the account derived from seed
+pub fn get_utxo_from_seed(seed: &str, id: u64) -> UTXO {
+	UTXO {
+		pub_key: H256::from_slice(get_from_seed::<AccountId>(seed).as_ref()),
+		id,
+		lock: Default::default(),
+	}
+}
+
+/// Helper function to generate one genesis UTXO per seed
+fn genesis_utxos(seeds: &[&str]) -> Vec<UTXO> {
+	seeds.iter().enumerate().map(|(id, seed)| get_utxo_from_seed(seed, id as u64)).collect()
+}
+
+/// Generator of the stateless accumulator. Use 2 as an arbitrary generator with "unknown" order.
+pub fn get_generator() -> U2048 {
+	U2048::from(2)
+}
+
 /// Helper function to generate stash, controller and session key from seed
 pub fn get_authority_keys_from_seed(seed: &str) -> (AccountId, AccountId, GrandpaId, BabeId) {
 	(
@@ -58,12 +80,13 @@ impl Alternative {
 					get_from_seed::<AccountId>("Alice//stash"),
 					get_from_seed::<AccountId>("Bob//stash"),
 				],
+				genesis_utxos(&["Alice", "Bob"]),
 				true),
 				vec![],
 				None,
 				None,
 				None,
-				None
+				Some(genesis_properties(genesis_utxos(&["Alice", "Bob"])))
 			),
 			Alternative::LocalTestnet => ChainSpec::from_genesis(
 				"Local Testnet",
@@ -87,12 +110,13 @@ impl Alternative {
 					get_from_seed::<AccountId>("Eve//stash"),
 					get_from_seed::<AccountId>("Ferdie//stash"),
 				],
+				genesis_utxos(&["Alice", "Bob", "Charlie", "Dave"]),
 				true),
 				vec![],
 				None,
 				None,
 				None,
-				None
+				Some(genesis_properties(genesis_utxos(&["Alice", "Bob", "Charlie", "Dave"])))
 			),
 		})
 	}
@@ -109,6 +133,7 @@ impl Alternative {
 fn testnet_genesis(initial_authorities: Vec<(AccountId, AccountId, GrandpaId, BabeId)>,
 	root_key: AccountId, 
 	endowed_accounts: Vec<AccountId>,
+	genesis_utxos: Vec<UTXO>,
 	_enable_println: bool) -> GenesisConfig {
 	GenesisConfig {
 		system: Some(SystemConfig {
@@ -131,5 +156,22 @@ fn testnet_genesis(initial_authorities: Vec<(AccountId, AccountId, GrandpaId, Ba
 		grandpa: Some(GrandpaConfig {
 			authorities: initial_authorities.iter().map(|x| (x.2.clone(), 1)).collect(),
 		}),
+		stateless: Some(StatelessConfig {
+			generator: get_generator(),
+			utxos: genesis_utxos,
+		}),
 	}
 }
+
+/// Exports the membership witness of every genesis UTXO in the chain specification properties
+/// (see `build-spec`) so that test networks start with spendable coins.
+fn genesis_properties(genesis_utxos: Vec<UTXO>) -> Properties {
+	let witnesses = stateless::genesis_witnesses(get_generator(), &genesis_utxos);
+	let coins: Vec<_> = genesis_utxos.iter().zip(witnesses.iter())
+		.map(|(utxo, witness)| json!({ "utxo": utxo, "witness": witness }))
+		.collect();
+
+	let mut properties = Properties::new();
+	properties.insert("genesisCoins".into(), json!(coins));
+	properties
+}
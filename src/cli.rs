@@ -1,20 +1,47 @@
 use crate::service;
 use futures::{future, Future, sync::oneshot};
 use std::cell::RefCell;
+use std::path::PathBuf;
 use tokio::runtime::Runtime;
 pub use substrate_cli::{VersionInfo, IntoExit, error};
-use substrate_cli::{informant, parse_and_prepare, ParseAndPrepare, NoCustom};
+use substrate_cli::{informant, parse_and_prepare, ParseAndPrepare, NoCustom, GetLogFilter};
 use substrate_service::{AbstractService, Roles as ServiceRoles};
-use crate::chain_spec;
+use sr_primitives::BuildStorage;
+use primitives::twox_128;
+use codec::Decode;
+use structopt::StructOpt;
+use crate::{chain_spec, stateless_validator};
 use log::info;
 
+/// Custom subcommands of the node.
+#[derive(Debug, StructOpt, Clone)]
+pub enum CustomCommand {
+	/// Validate the blocks exported by "export-blocks" while only keeping the state of the
+	/// accumulator of the stateless module.
+	#[structopt(name = "validate-stateless")]
+	ValidateStateless {
+		/// Input file of the blocks in the binary format of "export-blocks", starting at block 1.
+		#[structopt(parse(from_os_str))]
+		input: PathBuf,
+		/// Specify the chain specification (one of dev or local).
+		#[structopt(long = "chain", default_value = "local")]
+		chain: String,
+	},
+}
+
+impl GetLogFilter for CustomCommand {
+	fn get_log_filter(&self) -> Option<String> {
+		None
+	}
+}
+
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()> where
 	I: IntoIterator<Item = T>,
 	T: Into<std::ffi::OsString> + Clone,
 	E: IntoExit,
 {
-	match parse_and_prepare::<NoCustom, NoCustom, _>(&version, "substrate-node", args) {
+	match parse_and_prepare::<CustomCommand, NoCustom, _>(&version, "substrate-node", args) {
 		ParseAndPrepare::Run(cmd) => cmd.run::<(), _, _, _, _>(load_spec, exit,
 		|exit, _cli_args, _custom_args, config| {
 			info!("{}", version.name);
@@ -45,7 +72,8 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder::<(), _, _, _, _>(|config|
 			Ok(new_full_start!(config).0), load_spec),
-		ParseAndPrepare::CustomCommand(_) => Ok(())
+		ParseAndPrepare::CustomCommand(CustomCommand::ValidateStateless { input, chain }) =>
+			validate_stateless(input, &chain),
 	}?;
 
 	Ok(())
@@ -58,6 +86,24 @@ fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
 	})
 }
 
+/// Imports the exported blocks on a stateless validator, which starts from the genesis state of the
+/// accumulator in the chain specification.
+fn validate_stateless(input: PathBuf, chain: &str) -> error::Result<()> {
+	let spec = load_spec(chain).map_err(error::Error::Other)?
+		.ok_or_else(|| error::Error::Other(format!("Unknown chain specification {}", chain)))?;
+	let (storage, _) = spec.build_storage().map_err(error::Error::Other)?;
+	let genesis_state = storage.get(&twox_128(b"Stateless State")[..])
+		.and_then(|data| Decode::decode(&mut &data[..]).ok())
+		.ok_or_else(|| error::Error::Other("Genesis state of the accumulator is missing.".into()))?;
+
+	let data = std::fs::read(&input).map_err(|e| error::Error::Other(format!("{}", e)))?;
+	let validator = stateless_validator::validate_exported(genesis_state, &data)
+		.map_err(error::Error::Other)?;
+	let (number, hash) = validator.best();
+	info!("Validated blocks up to #{} ({}). State of the accumulator: {}", number, hash, validator.state());
+	Ok(())
+}
+
 fn run_until_exit<T, E>(
 	mut runtime: Runtime,
 	service: T,
@@ -0,0 +1,213 @@
+//! Helpers for reading the accumulator updates of a block from its events and extrinsics.
+
+use primitives::{twox_128, storage::StorageKey, Blake2Hasher, H256};
+use sr_primitives::{generic::BlockId, traits::Header as HeaderT};
+use substrate_client::{Client, CallExecutor, backend::Backend, error::{Error as ClientError, Result as ClientResult}};
+use codec::{Encode, Decode};
+use serde::{Serialize, Deserialize};
+use stateless_blockchain_runtime::{opaque::Block, stateless, swap, BlockNumber, Call, Event, Hash, UncheckedExtrinsic};
+use accumulator::{U2048, subroutines};
+
+/// Accumulator updates of a single block as emitted by the `Deletion` and `Addition` events.
+/// Each triple contains the state after the operation, the product of the affected elements and
+/// the proof of exponentiation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockDelta {
+	pub block_hash: H256,
+	pub deletion: Option<(U2048, U2048, U2048)>,
+	pub addition: Option<(U2048, U2048, U2048)>,
+}
+
+impl BlockDelta {
+	/// Returns true if the block did not modify the accumulator.
+	pub fn is_empty(&self) -> bool {
+		self.deletion.is_none() && self.addition.is_none()
+	}
+}
+
+/// Reads the events deposited in the given block.
+pub fn block_events<B, E, RA>(client: &Client<B, E, Block, RA>, hash: H256)
+	-> ClientResult<Vec<system::EventRecord<Event, Hash>>> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let key = StorageKey(twox_128(b"System Events").to_vec());
+	Ok(client.storage(&BlockId::Hash(hash), &key)?
+		.and_then(|data| Decode::decode(&mut &data.0[..]).ok())
+		.unwrap_or_default())
+}
+
+/// Collects the accumulator updates of the stateless module for the given block.
+pub fn block_delta<B, E, RA>(client: &Client<B, E, Block, RA>, hash: H256) -> ClientResult<BlockDelta> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	Ok(stateless_delta(hash, &block_events(client, hash)?))
+}
+
+/// Extracts the `Deletion` and `Addition` events of the stateless module from the event records of a block.
+pub fn stateless_delta(block_hash: H256, records: &[system::EventRecord<Event, Hash>]) -> BlockDelta {
+	let mut delta = BlockDelta { block_hash, deletion: None, addition: None };
+	for record in records {
+		match record.event {
+			Event::stateless(stateless::Event::Deletion(state, product, proof)) => {
+				delta.deletion = Some((state, product, proof));
+			},
+			Event::stateless(stateless::Event::Addition(state, product, proof)) => {
+				delta.addition = Some((state, product, proof));
+			},
+			_ => {},
+		}
+	}
+	delta
+}
+
+/// Decodes the extrinsics of the given block and returns the candidate elements that are deleted
+/// (spent and swapped UTXOs) and added (new UTXOs and minted coins) by the stateless module. Since an
+/// extrinsic may fail, the candidates should be checked against the products of the block delta.
+pub fn block_elems<B, E, RA>(client: &Client<B, E, Block, RA>, hash: H256) -> ClientResult<(Vec<U2048>, Vec<U2048>)> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	let mut deleted = Vec::new();
+	let mut added = Vec::new();
+	for extrinsic in client.block_body(&BlockId::Hash(hash))?.unwrap_or_default() {
+		// Opaque extrinsics drop the length prefix, so it is restored by encoding them again.
+		let extrinsic: UncheckedExtrinsic = match Decode::decode(&mut &extrinsic.encode()[..]) {
+			Ok(extrinsic) => extrinsic,
+			Err(_) => continue,
+		};
+		match extrinsic.function {
+			Call::Stateless(stateless::Call::addTransaction(transaction)) => {
+				deleted.push(subroutines::hash_to_prime(&transaction.input.encode()));
+				added.push(subroutines::hash_to_prime(&transaction.output.encode()));
+			},
+			Call::Stateless(stateless::Call::mint(elem)) => added.push(U2048::from(elem)),
+			Call::Swap(swap::Call::swap_to_account(burn)) => {
+				deleted.push(subroutines::hash_to_prime(&burn.input.encode()));
+			},
+			Call::Swap(swap::Call::swap_to_utxo(debit)) => {
+				added.push(subroutines::hash_to_prime(&debit.output.encode()));
+			},
+			_ => {},
+		}
+	}
+	Ok((deleted, added))
+}
+
+/// Returns the hashes of the blocks after "last" up to and including "hash" in ascending order.
+/// If "last" is `None`, the route starts right after the genesis block.
+pub fn route_from<B, E, RA>(client: &Client<B, E, Block, RA>, last: Option<H256>, hash: H256)
+	-> ClientResult<Vec<H256>> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+{
+	route_with(last, hash, |current| {
+		let header = client.header(&BlockId::Hash(current))?
+			.ok_or_else(|| ClientError::UnknownBlock(format!("{:?}", current)))?;
+		Ok((*header.number(), *header.parent_hash()))
+	})
+}
+
+/// Same as "route_from", where "header_of" returns the number and the parent hash of a block.
+pub fn route_with<F>(last: Option<H256>, hash: H256, mut header_of: F) -> ClientResult<Vec<H256>> where
+	F: FnMut(H256) -> ClientResult<(BlockNumber, H256)>,
+{
+	let mut route = Vec::new();
+	let mut current = hash;
+	while Some(current) != last {
+		let (number, parent_hash) = header_of(current)?;
+		if number == 0 {
+			if last.is_some() {
+				return Err(ClientError::Msg("Last processed block is not an ancestor of the given block.".into()));
+			}
+			break;
+		}
+		route.push(current);
+		current = parent_hash;
+	}
+	route.reverse();
+	Ok(route)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+
+	fn record(event: Event) -> system::EventRecord<Event, Hash> {
+		system::EventRecord { phase: system::Phase::ApplyExtrinsic(0), event, topics: vec![] }
+	}
+
+	#[test]
+	fn test_stateless_delta() {
+		let hash = H256::from_low_u64_be(1);
+		let deletion = (U2048::from(2), U2048::from(3), U2048::from(4));
+		let addition = (U2048::from(5), U2048::from(7), U2048::from(6));
+		let records = vec![
+			record(Event::stateless(stateless::Event::Deletion(deletion.0, deletion.1, deletion.2))),
+			record(Event::vector_commitment(vector_commitment::Event::Addition(U2048::from(8), U2048::from(9), U2048::from(10)))),
+			record(Event::stateless(stateless::Event::Addition(addition.0, addition.1, addition.2))),
+		];
+
+		let delta = stateless_delta(hash, &records);
+		assert_eq!(delta, BlockDelta { block_hash: hash, deletion: Some(deletion), addition: Some(addition) });
+
+		let delta = stateless_delta(hash, &[]);
+		assert_eq!(delta.is_empty(), true);
+	}
+
+	// Builds a chain of "len" blocks on top of the genesis block(hash 0) along with a fork of "fork_len"
+	// blocks that branches off at block 1.
+	fn chain(len: u64, fork_len: u64) -> HashMap<H256, (BlockNumber, H256)> {
+		let mut headers = HashMap::new();
+		headers.insert(H256::from_low_u64_be(0), (0, H256::zero()));
+		for number in 1..=len {
+			headers.insert(H256::from_low_u64_be(number), (number as BlockNumber, H256::from_low_u64_be(number - 1)));
+		}
+		for offset in 0..fork_len {
+			let parent = if offset == 0 { H256::from_low_u64_be(1) } else { H256::from_low_u64_be(100 + offset - 1) };
+			headers.insert(H256::from_low_u64_be(100 + offset), (offset as BlockNumber + 2, parent));
+		}
+		headers
+	}
+
+	fn route(headers: &HashMap<H256, (BlockNumber, H256)>, last: Option<u64>, hash: u64) -> ClientResult<Vec<H256>> {
+		route_with(last.map(H256::from_low_u64_be), H256::from_low_u64_be(hash), |current| {
+			headers.get(&current).cloned().ok_or_else(|| ClientError::UnknownBlock(format!("{:?}", current)))
+		})
+	}
+
+	#[test]
+	fn test_route() {
+		let headers = chain(5, 2);
+		let hashes = |numbers: &[u64]| numbers.iter().map(|number| H256::from_low_u64_be(*number)).collect::<Vec<_>>();
+
+		// Routes start after the genesis block or after the last processed block.
+		assert_eq!(route(&headers, None, 3).unwrap(), hashes(&[1, 2, 3]));
+		assert_eq!(route(&headers, Some(2), 5).unwrap(), hashes(&[3, 4, 5]));
+		assert_eq!(route(&headers, Some(5), 5).unwrap(), hashes(&[]));
+		assert_eq!(route(&headers, None, 0).unwrap(), hashes(&[]));
+		assert_eq!(route(&headers, Some(1), 101).unwrap(), hashes(&[100, 101]));
+
+		// The last processed block must be an ancestor of the given block.
+		assert_eq!(route(&headers, Some(3), 101).is_err(), true);
+		assert_eq!(route(&headers, Some(4), 2).is_err(), true);
+		assert_eq!(route(&headers, None, 42).is_err(), true);
+	}
+
+	#[test]
+	fn test_delta_json() {
+		let delta = BlockDelta {
+			block_hash: H256::from_low_u64_be(1),
+			deletion: None,
+			addition: Some((U2048::from(5), U2048::from(7), U2048::from(255))),
+		};
+		let json = serde_json::to_value(&delta).unwrap();
+		assert_eq!(json["addition"], serde_json::json!(["0x5", "0x7", "0xff"]));
+		assert_eq!(json["deletion"], serde_json::Value::Null);
+		assert_eq!(json.get("blockHash").is_some(), true);
+		assert_eq!(serde_json::from_value::<BlockDelta>(json).unwrap(), delta);
+	}
+}
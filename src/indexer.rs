@@ -0,0 +1,328 @@
+//! Archive indexer. Since the chain only keeps the latest state of the accumulator, the indexer
+//! records the accumulator updates of every finalized block so that users who have been offline
+//! can recover their witnesses. Records are appended to a local JSON lines file.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use futures::{Future, Stream};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use log::warn;
+use parking_lot::RwLock;
+use serde::{Serialize, Deserialize};
+use primitives::{Blake2Hasher, H256};
+use sr_primitives::{generic::BlockId, traits::ProvideRuntimeApi};
+use substrate_client::{Client, CallExecutor, BlockchainEvents, backend::Backend};
+use stateless_blockchain_runtime::{opaque::Block, stateless::StatelessApi, BlockNumber};
+use accumulator::{U2048, witnesses};
+use crate::events;
+
+/// Accumulator updates of a single block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockRecord {
+	pub number: BlockNumber,
+	pub hash: H256,
+	pub state_before: U2048,
+	pub state_after: U2048,
+	/// Product of the deleted elements.
+	pub deletions: U2048,
+	/// Product of the added elements.
+	pub additions: U2048,
+	pub deleted_elems: Vec<U2048>,
+	pub added_elems: Vec<U2048>,
+}
+
+/// Loads the records stored in the index file at "path". Only the consistent sequence of records
+/// starting at block 1 is kept. The file is truncated to these records, so the index is rebuilt from
+/// the first corrupted record onward.
+pub fn load_records(path: &Path) -> Vec<BlockRecord> {
+	let mut records: Vec<BlockRecord> = Vec::new();
+	if let Ok(data) = fs::read_to_string(path) {
+		for line in data.lines() {
+			match serde_json::from_str::<BlockRecord>(line) {
+				Ok(record) if record.number as usize == records.len() + 1 => records.push(record),
+				_ => {
+					warn!("Index at {:?} is corrupted after block {}", path, records.len());
+					if let Err(e) = write_records(path, &records) {
+						warn!("Failed to truncate the index at {:?}: {:?}", path, e);
+					}
+					break;
+				},
+			}
+		}
+	}
+	records
+}
+
+/// Replaces the index file at "path" with the given records.
+fn write_records(path: &Path, records: &[BlockRecord]) -> io::Result<()> {
+	let mut data = String::new();
+	for record in records {
+		data.push_str(&serde_json::to_string(record).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?);
+		data.push('\n');
+	}
+	let tmp_path = path.with_extension("tmp");
+	fs::write(&tmp_path, data)?;
+	fs::rename(&tmp_path, path)
+}
+
+/// Appends a record to the index file at "path".
+pub fn append_record(path: &Path, record: &BlockRecord) -> io::Result<()> {
+	let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+	let line = serde_json::to_string(record).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+	writeln!(file, "{}", line)
+}
+
+/// The archive indexer.
+pub struct Indexer<B, E, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	path: PathBuf,
+	/// Records of every finalized block in ascending order, starting at block 1.
+	records: RwLock<Vec<BlockRecord>>,
+}
+
+impl<B, E, RA> Indexer<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: StatelessApi<Block>,
+{
+	/// Creates the indexer and loads the records stored at "path" if they exist.
+	pub fn new(client: Arc<Client<B, E, Block, RA>>, path: PathBuf) -> Self {
+		let records = load_records(&path);
+		Indexer {
+			client,
+			path,
+			records: RwLock::new(records),
+		}
+	}
+
+	/// Returns a future that indexes every finalized block.
+	pub fn follow_finalized(self: Arc<Self>) -> impl Future<Item = (), Error = ()> {
+		self.client.finality_notification_stream().for_each(move |notification| {
+			if let Err(e) = self.on_finalized(notification.hash) {
+				warn!("Indexer failed to process block {:?}: {:?}", notification.hash, e);
+			}
+			Ok(())
+		})
+	}
+
+	/// Indexes every block between the last indexed block and the newly finalized block.
+	fn on_finalized(&self, hash: H256) -> std::result::Result<(), String> {
+		let last = self.records.read().last().map(|record| (record.hash, record.state_after));
+		let route = events::route_from(&*self.client, last.map(|(hash, _)| hash), hash)
+			.map_err(|e| format!("{:?}", e))?;
+
+		let mut state_before = match last {
+			Some((_, state)) => state,
+			None => self.client.runtime_api().get_state(&BlockId::Number(0))
+				.map_err(|e| format!("{:?}", e))?,
+		};
+
+		for block in route {
+			let record = self.index_block(block, state_before).map_err(|e| format!("{:?}", e))?;
+			append_record(&self.path, &record).map_err(|e| format!("{:?}", e))?;
+			state_before = record.state_after;
+			self.records.write().push(record);
+		}
+		Ok(())
+	}
+
+	/// Builds the record of a single block.
+	fn index_block(&self, hash: H256, state_before: U2048) -> substrate_client::error::Result<BlockRecord> {
+		let number = self.records.read().len() as BlockNumber + 1;
+		let delta = events::block_delta(&*self.client, hash)?;
+		let (deleted, added) = events::block_elems(&*self.client, hash)?;
+
+		let deletions = delta.deletion.map(|(_, product, _)| product).unwrap_or(U2048::from(1));
+		let additions = delta.addition.map(|(_, product, _)| product).unwrap_or(U2048::from(1));
+		let state_after = delta.addition.or(delta.deletion).map(|(state, _, _)| state).unwrap_or(state_before);
+
+		// Only keep the elements of the extrinsics that succeeded. Zero is never a valid element.
+		let zero = U2048::from(0);
+		let deleted_elems = deleted.into_iter().filter(|elem| *elem != zero && deletions % *elem == zero).collect();
+		let added_elems = added.into_iter().filter(|elem| *elem != zero && additions % *elem == zero).collect();
+
+		Ok(BlockRecord {
+			number,
+			hash,
+			state_before,
+			state_after,
+			deletions,
+			additions,
+			deleted_elems,
+			added_elems,
+		})
+	}
+
+	/// Returns the record of a block.
+	pub fn record(&self, number: BlockNumber) -> Option<BlockRecord> {
+		if number == 0 {
+			return None;
+		}
+		self.records.read().get(number as usize - 1).cloned()
+	}
+
+	/// Returns the records of the blocks in the range [from, to]. If "to" is `None`, the range ends
+	/// at the last indexed block.
+	pub fn records(&self, from: BlockNumber, to: Option<BlockNumber>) -> Vec<BlockRecord> {
+		let records = self.records.read();
+		let from = from.max(1) as usize;
+		let to = to.map(|to| to as usize).unwrap_or(records.len()).min(records.len());
+		if from > to {
+			return Vec::new();
+		}
+		records[from - 1..to].to_vec()
+	}
+
+	/// Merges the accumulator updates of the blocks in the range [from, to] into a single
+	/// (additions, deletions, new_state) triple. Returns `None` unless every block in the range has
+	/// been indexed, if an element is deleted and added again within the range or if the merged
+	/// products do not fit in a U2048(see `witnesses::merge_deltas`).
+	pub fn merged_delta(&self, from: BlockNumber, to: BlockNumber) -> Option<(U2048, U2048, U2048)> {
+		let records = self.records.read();
+		if from == 0 || from > to || to as usize > records.len() {
+			return None;
+		}
+		let deltas: Vec<(U2048, U2048, U2048)> = records[from as usize - 1..to as usize].iter()
+			.map(|record| (record.additions, record.deletions, record.state_after))
+			.collect();
+		witnesses::merge_deltas(&deltas)
+	}
+}
+
+/// Indexer RPC methods.
+#[rpc]
+pub trait IndexerApi {
+	/// Returns the accumulator updates of a block.
+	#[rpc(name = "indexer_getRecord")]
+	fn get_record(&self, number: BlockNumber) -> Result<Option<BlockRecord>>;
+
+	/// Returns the accumulator updates of every block in the range [from, to].
+	#[rpc(name = "indexer_getRecords")]
+	fn get_records(&self, from: BlockNumber, to: Option<BlockNumber>) -> Result<Vec<BlockRecord>>;
+
+	/// Returns the (additions, deletions, new_state) triple that updates a witness valid at block
+	/// "from - 1" to block "to". Fails if an element is deleted and added again within the range or
+	/// if the range is too large to be merged.
+	#[rpc(name = "indexer_getMergedDelta")]
+	fn get_merged_delta(&self, from: BlockNumber, to: BlockNumber) -> Result<(U2048, U2048, U2048)>;
+}
+
+/// Error returned when a block range is invalid or has not been indexed yet.
+fn invalid_range_error() -> Error {
+	Error {
+		code: ErrorCode::InvalidParams,
+		message: "Invalid block range.".into(),
+		data: None,
+	}
+}
+
+impl<B, E, RA> IndexerApi for Arc<Indexer<B, E, RA>> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: StatelessApi<Block>,
+{
+	fn get_record(&self, number: BlockNumber) -> Result<Option<BlockRecord>> {
+		Ok(self.record(number))
+	}
+
+	fn get_records(&self, from: BlockNumber, to: Option<BlockNumber>) -> Result<Vec<BlockRecord>> {
+		if let Some(to) = to {
+			if to < from {
+				return Err(invalid_range_error());
+			}
+		}
+		Ok(self.records(from, to))
+	}
+
+	fn get_merged_delta(&self, from: BlockNumber, to: BlockNumber) -> Result<(U2048, U2048, U2048)> {
+		if from == 0 || from > to || self.record(to).is_none() {
+			return Err(invalid_range_error());
+		}
+		self.merged_delta(from, to).ok_or_else(|| Error {
+			code: ErrorCode::InvalidParams,
+			message: "The range cannot be merged since an element is deleted and added again or the merged products \
+				overflow. Apply the records block by block.".into(),
+			data: None,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn record(number: BlockNumber) -> BlockRecord {
+		let elem = U2048::from(3 + 2 * number as u64);
+		BlockRecord {
+			number,
+			hash: H256::from_low_u64_be(number as u64),
+			state_before: U2048::from(number),
+			state_after: U2048::from(number + 1),
+			deletions: U2048::from(1),
+			additions: elem,
+			deleted_elems: vec![],
+			added_elems: vec![elem],
+		}
+	}
+
+	fn temp_path(name: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("indexer-{}-{}.jsonl", name, std::process::id()));
+		let _ = fs::remove_file(&path);
+		path
+	}
+
+	#[test]
+	fn test_reload_records() {
+		let path = temp_path("reload");
+		assert_eq!(load_records(&path), vec![]);
+
+		let records: Vec<BlockRecord> = (1..=3).map(record).collect();
+		for record in records.iter() {
+			append_record(&path, record).unwrap();
+		}
+		assert_eq!(load_records(&path), records);
+
+		// Records are appended to the existing file.
+		append_record(&path, &record(4)).unwrap();
+		assert_eq!(load_records(&path).len(), 4);
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_reload_corrupted_records() {
+		// A record that does not continue the sequence ends the index.
+		let path = temp_path("gap");
+		for number in &[1, 2, 4, 5] {
+			append_record(&path, &record(*number)).unwrap();
+		}
+		assert_eq!(load_records(&path), vec![record(1), record(2)]);
+		fs::remove_file(&path).unwrap();
+
+		// So does a partially written line.
+		let path = temp_path("truncated");
+		append_record(&path, &record(1)).unwrap();
+		let line = serde_json::to_string(&record(2)).unwrap();
+		let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+		write!(file, "{}", &line[..line.len() / 2]).unwrap();
+		assert_eq!(load_records(&path), vec![record(1)]);
+
+		// The corrupted line has been dropped, so the index can be continued.
+		append_record(&path, &record(2)).unwrap();
+		assert_eq!(load_records(&path), vec![record(1), record(2)]);
+		fs::remove_file(&path).unwrap();
+
+		// The index must start at block 1.
+		let path = temp_path("start");
+		append_record(&path, &record(2)).unwrap();
+		assert_eq!(load_records(&path), vec![]);
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -7,6 +7,13 @@ mod chain_spec;
 #[macro_use]
 mod service;
 mod cli;
+mod accumulator_import;
+mod events;
+mod indexer;
+mod prime_cache;
+mod rpc;
+mod stateless_validator;
+mod witness_service;
 
 pub use substrate_cli::{VersionInfo, IntoExit, error};
 
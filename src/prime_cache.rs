@@ -0,0 +1,51 @@
+//! Reads the prime representations of UTXOs that the off-chain worker of the stateless module caches
+//! in the off-chain local storage. The worker stores the primes of the UTXOs created in every block,
+//! so that clients can look them up through `stateless_hashToPrime` instead of computing them.
+
+use codec::Decode;
+use parking_lot::Mutex;
+use primitives::offchain::{OffchainStorage, STORAGE_PREFIX};
+use stateless_blockchain_runtime::stateless;
+use accumulator::{U2048, subroutines};
+
+/// Cache of prime representations backed by the off-chain local storage.
+pub struct PrimeCache<S> {
+	storage: Mutex<S>,
+}
+
+impl<S: OffchainStorage> PrimeCache<S> {
+	/// Creates a new cache on top of the given off-chain storage.
+	pub fn new(storage: S) -> Self {
+		PrimeCache { storage: Mutex::new(storage) }
+	}
+
+	/// Returns the cached prime representation of a SCALE encoded element.
+	pub fn get(&self, elem: &[u8]) -> Option<U2048> {
+		self.storage.lock().get(STORAGE_PREFIX, &stateless::prime_cache_key(elem))
+			.and_then(|data| Decode::decode(&mut &data[..]).ok())
+	}
+
+	/// Returns the cached prime representation of an element or computes it if it is not cached.
+	pub fn get_or_compute(&self, elem: &[u8]) -> U2048 {
+		self.get(elem).unwrap_or_else(|| subroutines::hash_to_prime(elem))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use primitives::offchain::InMemOffchainStorage;
+
+	#[test]
+	fn test_get() {
+		let mut storage = InMemOffchainStorage::default();
+		let cached = subroutines::hash_to_prime(&[1]);
+		storage.set(STORAGE_PREFIX, &stateless::prime_cache_key(&[1]), &cached.encode());
+		let cache = PrimeCache::new(storage);
+
+		assert_eq!(cache.get(&[1]), Some(cached));
+		assert_eq!(cache.get(&[2]), None);
+		assert_eq!(cache.get_or_compute(&[2]), subroutines::hash_to_prime(&[2]));
+	}
+}
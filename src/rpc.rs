@@ -0,0 +1,216 @@
+//! Custom RPC endpoints of the stateless blockchain node. These expose the native accumulator
+//! crate so that clients no longer have to compute witnesses in WASM.
+
+use std::collections::HashMap;
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use futures::{Future, Sink, Stream, sync::{mpsc, oneshot}};
+use futures::future::{Executor, ExecuteError, ExecuteErrorKind};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
+use log::warn;
+use parking_lot::Mutex;
+use primitives::{Blake2Hasher, Bytes};
+use sr_primitives::traits::ProvideRuntimeApi;
+use substrate_client::{Client, CallExecutor, BlockchainEvents, backend::Backend};
+use substrate_rpc::Metadata;
+use stateless_blockchain_runtime::{opaque::Block, stateless::StatelessApi};
+use accumulator::{U2048, subroutines, witnesses};
+use crate::events::{self, BlockDelta};
+use crate::indexer::{Indexer, IndexerApi};
+use crate::prime_cache::PrimeCache;
+use crate::witness_service::{WitnessService, WitnessServiceApi};
+
+/// A boxed task spawned on behalf of a subscription.
+pub type RpcTask = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Executor used by the RPC extensions to spawn subscription tasks.
+pub type TaskExecutor = Arc<dyn Executor<RpcTask> + Send + Sync>;
+
+/// The RPC extensions are created before the service is built, so subscription tasks are
+/// forwarded over a channel and spawned once the service is running.
+pub struct TaskForwarder(Mutex<mpsc::UnboundedSender<RpcTask>>);
+
+impl TaskForwarder {
+	/// Creates a new forwarder along with the stream of tasks that should be spawned.
+	pub fn new() -> (TaskExecutor, mpsc::UnboundedReceiver<RpcTask>) {
+		let (sender, receiver) = mpsc::unbounded();
+		(Arc::new(TaskForwarder(Mutex::new(sender))), receiver)
+	}
+}
+
+impl Executor<RpcTask> for TaskForwarder {
+	fn execute(&self, future: RpcTask) -> std::result::Result<(), ExecuteError<RpcTask>> {
+		self.0.lock().unbounded_send(future)
+			.map_err(|e| ExecuteError::new(ExecuteErrorKind::Shutdown, e.into_inner()))
+	}
+}
+
+/// Stateless RPC methods.
+#[rpc]
+pub trait StatelessRpcApi {
+	/// RPC metadata
+	type Metadata;
+
+	/// Hashes the (SCALE encoded) input to a prime accumulator element. The primes of the UTXOs
+	/// created on-chain are read from the cache of the off-chain worker.
+	#[rpc(name = "stateless_hashToPrime")]
+	fn hash_to_prime(&self, elem: Bytes) -> Result<U2048>;
+
+	/// Creates the membership witness of "elem" given the state before a batch of elements was
+	/// added and the product of the added elements.
+	#[rpc(name = "stateless_createWitness")]
+	fn create_witness(&self, old_state: U2048, agg: U2048, elem: U2048) -> Result<U2048>;
+
+	/// Updates a membership witness given the products of the untracked additions and deletions.
+	#[rpc(name = "stateless_updateWitness")]
+	fn update_witness(&self, elem: U2048, witness: U2048, new_state: U2048, additions: U2048, deletions: U2048)
+		-> Result<U2048>;
+
+	/// Verifies the membership witness of an element against a state.
+	#[rpc(name = "stateless_verifyWitness")]
+	fn verify_witness(&self, state: U2048, witness: U2048, elem: U2048) -> Result<bool>;
+
+	/// Subscribes to the deletion/addition triples of every imported block.
+	#[pubsub(subscription = "stateless_delta", subscribe, name = "stateless_subscribeDeltas")]
+	fn subscribe_deltas(&self, metadata: Self::Metadata, subscriber: Subscriber<BlockDelta>);
+
+	/// Unsubscribes from the deletion/addition triples.
+	#[pubsub(subscription = "stateless_delta", unsubscribe, name = "stateless_unsubscribeDeltas")]
+	fn unsubscribe_deltas(&self, metadata: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool>;
+}
+
+/// Implementation of the stateless RPC methods.
+pub struct StatelessRpc<B: Backend<Block, Blake2Hasher>, E, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	executor: TaskExecutor,
+	next_id: AtomicUsize,
+	subscriptions: Mutex<HashMap<usize, oneshot::Sender<()>>>,
+	prime_cache: Option<PrimeCache<B::OffchainStorage>>,
+}
+
+impl<B: Backend<Block, Blake2Hasher>, E, RA> StatelessRpc<B, E, RA> {
+	/// Creates a new instance of the stateless RPC handler.
+	pub fn new(client: Arc<Client<B, E, Block, RA>>, executor: TaskExecutor) -> Self {
+		#[allow(deprecated)]
+		let prime_cache = client.backend().offchain_storage().map(PrimeCache::new);
+		StatelessRpc {
+			client,
+			executor,
+			next_id: AtomicUsize::new(0),
+			subscriptions: Mutex::new(HashMap::new()),
+			prime_cache,
+		}
+	}
+}
+
+/// Error returned when an element is not part of the aggregated value.
+fn invalid_elem_error() -> Error {
+	Error {
+		code: ErrorCode::InvalidParams,
+		message: "Element is not contained in the aggregated value.".into(),
+		data: None,
+	}
+}
+
+/// Rejects elements that are not prime, since witnesses are only meaningful for accumulator elements.
+fn check_elem(elem: U2048) -> Result<()> {
+	if elem < U2048::from(2) || !subroutines::miller_rabin(elem) {
+		return Err(Error::invalid_params("Element is not prime."));
+	}
+	Ok(())
+}
+
+impl<B, E, RA> StatelessRpcApi for StatelessRpc<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	type Metadata = Metadata;
+
+	fn hash_to_prime(&self, elem: Bytes) -> Result<U2048> {
+		Ok(match &self.prime_cache {
+			Some(cache) => cache.get_or_compute(&elem),
+			None => subroutines::hash_to_prime(&elem),
+		})
+	}
+
+	fn create_witness(&self, old_state: U2048, agg: U2048, elem: U2048) -> Result<U2048> {
+		check_elem(elem)?;
+		witnesses::mem_wit_create(old_state, agg, elem).ok_or_else(invalid_elem_error)
+	}
+
+	fn update_witness(&self, elem: U2048, witness: U2048, new_state: U2048, additions: U2048, deletions: U2048)
+		-> Result<U2048>
+	{
+		check_elem(elem)?;
+		witnesses::update_mem_wit(elem, witness, new_state, additions, deletions)
+			.ok_or_else(|| Error::invalid_params("Witness cannot be updated with the given deltas."))
+	}
+
+	fn verify_witness(&self, state: U2048, witness: U2048, elem: U2048) -> Result<bool> {
+		Ok(witnesses::verify_mem_wit(state, witness, elem))
+	}
+
+	fn subscribe_deltas(&self, _metadata: Self::Metadata, subscriber: Subscriber<BlockDelta>) {
+		let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+		let sink = match subscriber.assign_id(SubscriptionId::Number(id as u64)) {
+			Ok(sink) => sink,
+			Err(_) => return,
+		};
+
+		let client = self.client.clone();
+		let deltas = self.client.import_notification_stream()
+			.filter_map(move |notification| events::block_delta(&*client, notification.hash).ok())
+			.filter(|delta| !delta.is_empty())
+			.map(|delta| Ok(delta))
+			.map_err(|e| warn!("Block notification stream error: {:?}", e));
+
+		let (cancel_sender, cancel_receiver) = oneshot::channel();
+		let task = sink
+			.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+			.send_all(deltas)
+			.map(|_| ())
+			.select(cancel_receiver.map_err(|_| ()))
+			.map(|_| ())
+			.map_err(|_| ());
+
+		if self.executor.execute(Box::new(task)).is_ok() {
+			self.subscriptions.lock().insert(id, cancel_sender);
+		}
+	}
+
+	fn unsubscribe_deltas(&self, _metadata: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+		let removed = match id {
+			SubscriptionId::Number(id) => self.subscriptions.lock().remove(&(id as usize)),
+			SubscriptionId::String(_) => None,
+		};
+		Ok(match removed {
+			Some(cancel) => {
+				let _ = cancel.send(());
+				true
+			},
+			None => false,
+		})
+	}
+}
+
+/// Instantiates all of the custom RPC extensions.
+pub fn create<B, E, RA>(
+	client: Arc<Client<B, E, Block, RA>>,
+	executor: TaskExecutor,
+	witness_service: Arc<WitnessService<B, E, RA>>,
+	indexer: Arc<Indexer<B, E, RA>>,
+) -> jsonrpc_core::IoHandler<Metadata> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: StatelessApi<Block>,
+{
+	let mut io = jsonrpc_core::IoHandler::default();
+	io.extend_with(StatelessRpcApi::to_delegate(StatelessRpc::new(client, executor)));
+	io.extend_with(WitnessServiceApi::to_delegate(witness_service));
+	io.extend_with(IndexerApi::to_delegate(indexer));
+	io
+}
@@ -2,7 +2,7 @@
 
 use std::sync::Arc;
 use std::time::Duration;
-use substrate_client::LongestChain;
+use substrate_client::{LongestChain, backend::Backend};
 use babe::{import_queue, start_babe, Config};
 use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
 use futures::prelude::*;
@@ -35,6 +35,10 @@ macro_rules! new_full_start {
 		let mut import_setup = None;
 		let inherent_data_providers = inherents::InherentDataProviders::new();
 		let mut tasks_to_spawn = None;
+		let (rpc_executor, rpc_tasks) = crate::rpc::TaskForwarder::new();
+		let mut stateless_services = None;
+		let witness_db_path = $config.database_path.with_file_name("witness_service.json");
+		let index_path = $config.database_path.with_file_name("accumulator_index.jsonl");
 
 		let builder = substrate_service::ServiceBuilder::new_full::<
 			stateless_blockchain_runtime::opaque::Block, stateless_blockchain_runtime::RuntimeApi, crate::service::Executor
@@ -55,6 +59,11 @@ macro_rules! new_full_start {
 					)?;
 				let justification_import = block_import.clone();
 
+				// check the accumulator transitions committed in the headers against the executed state
+				let block_import = crate::accumulator_import::AccumulatorBlockImport::new_full(
+					block_import, client.clone()
+				);
+
 				let (import_queue, babe_link, babe_block_import, pruning_task) = babe::import_queue(
 					babe::Config::get_or_compute(&*client)?,
 					block_import,
@@ -70,9 +79,19 @@ macro_rules! new_full_start {
 				tasks_to_spawn = Some(vec![Box::new(pruning_task)]);
 
 				Ok(import_queue)
+			})?
+			.with_rpc_extensions(|client, _pool| {
+				let service = std::sync::Arc::new(
+					crate::witness_service::WitnessService::new(client.clone(), witness_db_path)
+				);
+				let indexer = std::sync::Arc::new(
+					crate::indexer::Indexer::new(client.clone(), index_path)
+				);
+				stateless_services = Some((service.clone(), indexer.clone()));
+				crate::rpc::create(client, rpc_executor, service, indexer)
 			})?;
 
-		(builder, import_setup, inherent_data_providers, tasks_to_spawn)
+		(builder, import_setup, inherent_data_providers, tasks_to_spawn, rpc_tasks, stateless_services)
 	}}
 }
 
@@ -81,7 +100,8 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 	-> Result<impl AbstractService, ServiceError>
 {
 
-	let (builder, mut import_setup, inherent_data_providers, mut tasks_to_spawn) = new_full_start!(config);
+	let (builder, mut import_setup, inherent_data_providers, mut tasks_to_spawn, rpc_tasks, mut stateless_services) =
+		new_full_start!(config);
 
 	let service = builder.with_network_protocol(|_| Ok(NodeProtocol::new()))?
 		.with_finality_proof_provider(|client|
@@ -104,6 +124,19 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 		}
 	}
 
+	// spawn the tasks of the RPC subscriptions
+	service.spawn_task(rpc_tasks.for_each(|task| {
+		tokio::spawn(task);
+		Ok(())
+	}));
+
+	// follow finalized blocks to maintain the witnesses of the registered UTXOs and to index the
+	// accumulator updates
+	if let Some((witness_service, indexer)) = stateless_services.take() {
+		service.spawn_task(witness_service.follow_finalized());
+		service.spawn_task(indexer.follow_finalized());
+	}
+
 	if service.config().roles.is_authority() {
 		let proposer = basic_authorship::ProposerFactory {
 			client: service.client(),
@@ -208,6 +241,9 @@ pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisCo
 			let finality_proof_request_builder =
 				finality_proof_import.create_finality_proof_request_builder();
 
+			// verify the accumulator transitions committed in the headers
+			let block_import = crate::accumulator_import::AccumulatorBlockImport::new_light(block_import, client.clone());
+
 			// FIXME: pruning task isn't started since light client doesn't do `AuthoritySetup`.
 			let (import_queue, ..) = import_queue(
 				Config::get_or_compute(&*client)?,
@@ -0,0 +1,272 @@
+//! Execution mode of a validator that only keeps the state of the accumulator of the stateless
+//! module. Instead of executing the blocks with the runtime, the accumulator calls are decoded from
+//! the extrinsics and executed by `stateless::execute_stateless_block` against the state of the
+//! parent block. Neither the storage nor the system module are needed: the block number that the
+//! locks are checked against is taken from the header.
+//!
+//! NOTE: The other modules are not validated in this mode. In particular, the swaps from accounts
+//! are only checked against the transition committed in the digest.
+
+use std::collections::HashMap;
+use codec::{Encode, Decode};
+use consensus_common::{BlockImport, BlockImportParams, ImportResult, Error as ConsensusError, well_known_cache_keys::Id as CacheKeyId};
+use primitives::H256;
+use sr_primitives::{generic::SignedBlock, traits::{Block as BlockT, Header as HeaderT}};
+use stateless_blockchain_runtime::{opaque::Block, stateless::{self, Lock, StatelessBlock, StatelessCall}, swap, BlockNumber, Call, UncheckedExtrinsic};
+use accumulator::U2048;
+use crate::accumulator_import::accumulator_digest;
+
+/// Extracts the accumulator calls of a block along with the committed transition.
+pub fn stateless_block(block: &Block) -> Result<StatelessBlock, String> {
+	let digest = accumulator_digest(block.header())
+		.ok_or_else(|| format!("Block {:?} has no accumulator digest", block.header().hash()))?;
+
+	let mut calls = Vec::new();
+	for extrinsic in block.extrinsics() {
+		// Opaque extrinsics drop the length prefix, so it is restored by encoding them again.
+		let extrinsic: UncheckedExtrinsic = Decode::decode(&mut &extrinsic.encode()[..])
+			.map_err(|_| format!("Invalid extrinsic in block {:?}", block.header().hash()))?;
+		// The calls of the accumulator modules require a signed origin.
+		if extrinsic.signature.is_none() {
+			continue;
+		}
+		match extrinsic.function {
+			Call::Stateless(stateless::Call::addTransaction(transaction)) => {
+				calls.push(StatelessCall::Spend(transaction));
+			},
+			Call::Stateless(stateless::Call::mint(elem)) => calls.push(StatelessCall::Mint(elem)),
+			Call::Swap(swap::Call::swap_to_account(burn)) => {
+				if burn.verify_signature() && burn.input.lock == Lock::default() {
+					calls.push(StatelessCall::Burn(burn.input, burn.witness));
+				}
+			},
+			Call::Swap(swap::Call::swap_to_utxo(debit)) => calls.push(StatelessCall::Swapped(debit.output)),
+			_ => {},
+		}
+	}
+
+	Ok(StatelessBlock { number: *block.header().number() as u64, calls, digest })
+}
+
+/// Follows a single chain while only storing the state of the accumulator after its best block.
+pub struct StatelessValidator {
+	state: U2048,
+	best: (BlockNumber, H256),
+}
+
+impl StatelessValidator {
+	/// Starts from the genesis block and the state of the accumulator defined in the chain spec.
+	pub fn new(genesis_hash: H256, genesis_state: U2048) -> Self {
+		StatelessValidator { state: genesis_state, best: (0, genesis_hash) }
+	}
+
+	/// Returns the state of the accumulator after the best block.
+	pub fn state(&self) -> U2048 {
+		self.state
+	}
+
+	/// Returns the number and the hash of the best block.
+	pub fn best(&self) -> (BlockNumber, H256) {
+		self.best
+	}
+
+	/// Executes the accumulator calls of a child of the best block, which becomes the new best block.
+	pub fn import(&mut self, block: &Block) -> Result<(), String> {
+		let header = block.header();
+		if *header.parent_hash() != self.best.1 || *header.number() != self.best.0 + 1 {
+			return Err(format!("Block {:?} does not extend the best block", header.hash()));
+		}
+
+		let state = stateless::execute_stateless_block(self.state, &stateless_block(block)?)
+			.map_err(|e| format!("Block {:?} is invalid: {}", header.hash(), e))?;
+		self.state = state;
+		self.best = (*header.number(), header.hash());
+		Ok(())
+	}
+}
+
+/// Imports the blocks exported by the "export-blocks" subcommand in the binary format, i.e. the number
+/// of blocks followed by the encoded signed blocks. The export must start at block 1, whose parent
+/// is taken as the genesis block.
+pub fn validate_exported(genesis_state: U2048, data: &[u8]) -> Result<StatelessValidator, String> {
+	let input = &mut &data[..];
+	let count: u64 = Decode::decode(input).map_err(|_| "Invalid number of blocks".to_string())?;
+	let mut validator = None;
+	for _ in 0..count {
+		let block: SignedBlock<Block> = Decode::decode(input).map_err(|_| "Invalid block".to_string())?;
+		validator.get_or_insert_with(|| StatelessValidator::new(*block.block.header().parent_hash(), genesis_state))
+			.import(&block.block)?;
+	}
+	validator.ok_or_else(|| "No blocks to validate".to_string())
+}
+
+impl BlockImport<Block> for StatelessValidator {
+	type Error = ConsensusError;
+
+	fn check_block(&mut self, _hash: H256, parent_hash: H256) -> Result<ImportResult, Self::Error> {
+		if parent_hash != self.best.1 {
+			return Ok(ImportResult::UnknownParent);
+		}
+		Ok(ImportResult::Imported(Default::default()))
+	}
+
+	fn import_block(
+		&mut self,
+		block: BlockImportParams<Block>,
+		_new_cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let hash = block.header.hash();
+		if block.header.number() <= &self.best.0 {
+			return Ok(ImportResult::AlreadyInChain);
+		}
+		let extrinsics = block.body
+			.ok_or_else(|| ConsensusError::ClientImport(format!("Block {:?} has no body", hash)))?;
+		self.import(&Block::new(block.header, extrinsics))
+			.map_err(ConsensusError::ClientImport)?;
+		Ok(ImportResult::Imported(Default::default()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::{sr25519, Pair, H512};
+	use sr_primitives::{generic::Digest, OpaqueExtrinsic};
+	use stateless_blockchain_runtime::{opaque::Header, stateless::{AccumulatorDigest, Transaction, UTXO}};
+	use accumulator::{subroutines, MODULUS};
+
+	fn account(seed: u8) -> (sr25519::Pair, H256) {
+		let pair = sr25519::Pair::from_seed(&[seed; 32]);
+		let pub_key = H256::from_slice(pair.public().as_ref());
+		(pair, pub_key)
+	}
+
+	fn elem(utxo: &UTXO) -> U2048 {
+		subroutines::hash_to_prime(&utxo.encode())
+	}
+
+	fn spend(pair: &sr25519::Pair, input: UTXO, output: UTXO, witness: U2048) -> Call {
+		let mut witness_bytes = [0u8; 256];
+		witness.to_little_endian(&mut witness_bytes);
+		let mut transaction = Transaction { input, output, witness: witness_bytes.to_vec(), ..Default::default() };
+		transaction.signature = H512::from(pair.sign(&transaction.signing_payload()).0);
+		Call::Stateless(stateless::Call::addTransaction(transaction))
+	}
+
+	// The validator does not check the signatures of the extrinsics, so an index address, a zero
+	// signature, an immortal era, nonce 0 and no tip are used.
+	fn signed(call: Call) -> OpaqueExtrinsic {
+		let (address, signature, extra) = Decode::decode(&mut &[0u8; 68][..]).unwrap();
+		let extrinsic = UncheckedExtrinsic::new_signed(call, address, signature, extra);
+		Decode::decode(&mut &extrinsic.encode()[..]).unwrap()
+	}
+
+	fn unsigned(call: Call) -> OpaqueExtrinsic {
+		Decode::decode(&mut &UncheckedExtrinsic::new_unsigned(call).encode()[..]).unwrap()
+	}
+
+	// Builds a child of "parent" with the transition computed the same way as "on_finalize".
+	fn block(parent: &Header, state: U2048, extrinsics: Vec<OpaqueExtrinsic>, spent: Vec<(U2048, U2048)>, added: Vec<U2048>) -> Block {
+		let mut digest = AccumulatorDigest { state, deletion: None, addition: None };
+		if !spent.is_empty() {
+			let (state, agg, proof) = accumulator::batch_delete(digest.state, &spent);
+			digest.deletion = Some((state, agg, proof));
+			digest.state = state;
+		}
+		if !added.is_empty() {
+			let (state, agg, proof) = accumulator::batch_add(digest.state, &added);
+			digest.addition = Some((state, agg, proof));
+			digest.state = state;
+		}
+		let header = Header::new(
+			parent.number() + 1,
+			Default::default(),
+			Default::default(),
+			parent.hash(),
+			Digest { logs: vec![digest.to_digest_item()] },
+		);
+		Block::new(header, extrinsics)
+	}
+
+	#[test]
+	fn test_import_chain() {
+		let modulus = U2048::from_dec_str(MODULUS).unwrap();
+		let generator = U2048::from(2);
+		let (pair_0, key_0) = account(0);
+		let (pair_1, key_1) = account(1);
+		let (_, key_2) = account(2);
+		let utxo_0 = UTXO { pub_key: key_0, id: 0, lock: Lock::default() };
+		let utxo_1 = UTXO { pub_key: key_1, id: 1, lock: Lock::default() };
+		let utxo_2 = UTXO { pub_key: key_2, id: 2, lock: Lock::default() };
+		let utxo_3 = UTXO { pub_key: key_2, id: 3, lock: Lock::default() };
+		let utxo_4 = UTXO { pub_key: key_0, id: 4, lock: Lock::default() };
+
+		let genesis_state = stateless::genesis_state(generator, &[utxo_0, utxo_1]);
+		let genesis = Header::new(0, Default::default(), Default::default(), Default::default(), Default::default());
+		let mut validator = StatelessValidator::new(genesis.hash(), genesis_state);
+
+		// 1. Spend utxo_0 and mint 7. The unsigned mint fails on-chain and is not part of the transition.
+		let witness_0 = subroutines::mod_exp(generator, elem(&utxo_1), modulus);
+		let block_1 = block(&genesis, genesis_state, vec![
+			signed(spend(&pair_0, utxo_0, utxo_2, witness_0)),
+			signed(Call::Stateless(stateless::Call::mint(7))),
+			unsigned(Call::Stateless(stateless::Call::mint(11))),
+		], vec![(elem(&utxo_0), witness_0)], vec![elem(&utxo_2), U2048::from(7)]);
+
+		// A transition that skips a valid spend is rejected.
+		let forged = block(&genesis, genesis_state, block_1.extrinsics().to_vec(), vec![], vec![elem(&utxo_2), U2048::from(7)]);
+		assert_eq!(validator.import(&forged).is_err(), true);
+		// So is a transition that does not match the spends and mints.
+		let forged = block(&genesis, genesis_state, block_1.extrinsics().to_vec(), vec![(elem(&utxo_0), witness_0)], vec![U2048::from(7)]);
+		assert_eq!(validator.import(&forged).is_err(), true);
+
+		assert_eq!(validator.import(&block_1), Ok(()));
+		let state_1 = accumulator_digest(block_1.header()).unwrap().state;
+		assert_eq!(validator.state(), state_1);
+
+		// 2. Spend utxo_1 and create utxo_4 through a swap. A spend that is not signed by the owner
+		// fails on-chain.
+		let witness_1 = subroutines::mod_exp(generator, elem(&utxo_2) * U2048::from(7), modulus);
+		let debit = swap::AccountToUtxo { output: utxo_4, ..Default::default() };
+		let block_2 = block(block_1.header(), state_1, vec![
+			signed(spend(&pair_1, utxo_0, utxo_3, witness_0)),
+			signed(spend(&pair_1, utxo_1, utxo_3, witness_1)),
+			signed(Call::Swap(swap::Call::swap_to_utxo(debit))),
+		], vec![(elem(&utxo_1), witness_1)], vec![elem(&utxo_3), elem(&utxo_4)]);
+
+		// Blocks that do not extend the best block are rejected.
+		assert_eq!(validator.import(&block_1).is_err(), true);
+		let orphan = block(block_2.header(), state_1, vec![], vec![], vec![]);
+		assert_eq!(validator.import(&orphan).is_err(), true);
+
+		assert_eq!(validator.import(&block_2), Ok(()));
+		let state_2 = accumulator_digest(block_2.header()).unwrap().state;
+		assert_eq!(validator.state(), state_2);
+		assert_eq!(validator.best(), (2, block_2.header().hash()));
+
+		// 3. An empty block leaves the state unchanged.
+		let block_3 = block(block_2.header(), state_2, vec![], vec![], vec![]);
+		assert_eq!(validator.import(&block_3), Ok(()));
+		assert_eq!(validator.state(), state_2);
+
+		// The same chain exported by a full node.
+		let mut data = 3u64.encode();
+		for block in vec![block_1.clone(), block_2.clone(), block_3] {
+			data.extend(SignedBlock { block, justification: None }.encode());
+		}
+		let exported = validate_exported(genesis_state, &data).unwrap();
+		assert_eq!(exported.state(), validator.state());
+		assert_eq!(exported.best(), validator.best());
+		assert_eq!(validate_exported(genesis_state, &data[..data.len() - 1]).is_err(), true);
+		let mut data = 2u64.encode();
+		for block in vec![block_2.clone(), block_1] {
+			data.extend(SignedBlock { block, justification: None }.encode());
+		}
+		assert_eq!(validate_exported(genesis_state, &data).is_err(), true);
+
+		// The new UTXOs can be spent against the state kept by the validator.
+		let (deleted, _, _) = accumulator_digest(block_2.header()).unwrap().deletion.unwrap();
+		let witness_3 = subroutines::mod_exp(deleted, elem(&utxo_4), modulus);
+		assert_eq!(accumulator::witnesses::verify_mem_wit(validator.state(), witness_3, elem(&utxo_3)), true);
+	}
+}
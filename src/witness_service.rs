@@ -0,0 +1,375 @@
+//! Witness service provider. Follows finalized blocks and keeps the membership witnesses of a
+//! registered set of UTXOs up to date so that users do not have to watch the chain themselves.
+//! The registered UTXOs and their witnesses are persisted in a local JSON database.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use codec::Encode;
+use futures::{Future, Stream};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use log::{info, warn};
+use parking_lot::Mutex;
+use serde::{Serialize, Deserialize};
+use primitives::{Blake2Hasher, H256};
+use sr_primitives::{generic::BlockId, traits::ProvideRuntimeApi};
+use substrate_client::{Client, CallExecutor, BlockchainEvents, backend::Backend};
+use stateless_blockchain_runtime::{opaque::Block, stateless::{StatelessApi, UTXO}};
+use accumulator::{U2048, MODULUS, subroutines, witnesses};
+use crate::events::{self, BlockDelta};
+
+/// A registered UTXO. The witness is `None` until the UTXO has been added to the accumulator.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackedCoin {
+	pub utxo: UTXO,
+	pub elem: U2048,
+	pub witness: Option<U2048>,
+}
+
+/// Contents of the witness database.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WitnessDb {
+	/// Last processed (finalized) block along with the state of the accumulator at that block.
+	pub best: Option<(H256, U2048)>,
+	/// The registered UTXOs.
+	pub coins: Vec<TrackedCoin>,
+}
+
+impl WitnessDb {
+	/// Applies the accumulator updates of a block. Spent coins are dropped, the witnesses of the
+	/// remaining coins are updated and witnesses are created for newly added coins. Coins whose
+	/// witness cannot be updated are dropped as well, so that they can be registered again.
+	/// "state" represents the state of the accumulator before the block.
+	pub fn apply_delta(&mut self, state: U2048, delta: &BlockDelta) -> U2048 {
+		let (state_after_del, deletions) = match delta.deletion {
+			Some((new_state, product, _)) => (new_state, product),
+			None => (state, U2048::from(1)),
+		};
+		let (new_state, additions) = match delta.addition {
+			Some((new_state, product, _)) => (new_state, product),
+			None => (state_after_del, U2048::from(1)),
+		};
+
+		// Drop the coins that have been spent.
+		if deletions != U2048::from(1) {
+			self.coins.retain(|coin| coin.witness.is_none() || deletions % coin.elem != U2048::from(0));
+		}
+
+		// Update the witnesses of the coins that were already in the accumulator.
+		if additions != U2048::from(1) || deletions != U2048::from(1) {
+			let coins = std::mem::replace(&mut self.coins, Vec::new());
+			self.coins = coins.into_iter().filter_map(|mut coin| {
+				if let Some(witness) = coin.witness {
+					match witnesses::update_mem_wit(coin.elem, witness, new_state, additions, deletions) {
+						Some(witness) => coin.witness = Some(witness),
+						None => {
+							warn!("Witness service dropped UTXO {:?} since its witness could not be updated", coin.utxo);
+							return None;
+						},
+					}
+				}
+				Some(coin)
+			}).collect();
+		}
+
+		// Create witnesses for the registered coins that have just been added.
+		if additions != U2048::from(1) {
+			let added: Vec<usize> = self.coins.iter()
+				.enumerate()
+				.filter(|(_, coin)| coin.witness.is_none() && additions % coin.elem == U2048::from(0))
+				.map(|(index, _)| index)
+				.collect();
+
+			if !added.is_empty() {
+				let elems: Vec<U2048> = added.iter().map(|&index| self.coins[index].elem).collect();
+				// Account for the added elements that are not tracked by the service.
+				let untracked = additions / subroutines::prime_product(&elems);
+				let generator = subroutines::mod_exp(state_after_del, untracked, U2048::from_dec_str(MODULUS).unwrap());
+				let created = witnesses::create_all_mem_wit(generator, &elems);
+				for (&index, witness) in added.iter().zip(created.into_iter()) {
+					self.coins[index].witness = Some(witness);
+				}
+			}
+		}
+
+		new_state
+	}
+}
+
+/// The witness service provider.
+pub struct WitnessService<B, E, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	path: PathBuf,
+	db: Mutex<WitnessDb>,
+}
+
+impl<B, E, RA> WitnessService<B, E, RA> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: StatelessApi<Block>,
+{
+	/// Creates the service and loads the database stored at "path" if it exists.
+	pub fn new(client: Arc<Client<B, E, Block, RA>>, path: PathBuf) -> Self {
+		let db = match fs::read(&path) {
+			Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+				warn!("Witness database at {:?} is corrupted, starting from scratch: {:?}", path, e);
+				WitnessDb::default()
+			}),
+			Err(_) => WitnessDb::default(),
+		};
+
+		WitnessService {
+			client,
+			path,
+			db: Mutex::new(db),
+		}
+	}
+
+	/// Returns a future that processes every finalized block.
+	pub fn follow_finalized(self: Arc<Self>) -> impl Future<Item = (), Error = ()> {
+		self.client.finality_notification_stream().for_each(move |notification| {
+			if let Err(e) = self.on_finalized(notification.hash) {
+				warn!("Witness service failed to process block {:?}: {:?}", notification.hash, e);
+			}
+			Ok(())
+		})
+	}
+
+	/// Processes every block between the last processed block and the newly finalized block.
+	fn on_finalized(&self, hash: H256) -> std::result::Result<(), String> {
+		let mut db = self.db.lock();
+
+		let (best_hash, mut state) = match db.best {
+			Some(best) => best,
+			None => {
+				// Nothing has been processed yet so start tracking from this block.
+				db.best = Some((hash, self.state_at(hash)?));
+				return self.persist(&db).map_err(|e| format!("{:?}", e));
+			},
+		};
+
+		// Finality notifications are not necessarily sent for every block, so collect the route
+		// from the last processed block.
+		let route = events::route_from(&*self.client, Some(best_hash), hash).map_err(|e| format!("{:?}", e))?;
+
+		for block in route {
+			let delta = events::block_delta(&*self.client, block).map_err(|e| format!("{:?}", e))?;
+			state = db.apply_delta(state, &delta);
+			db.best = Some((block, state));
+		}
+
+		self.persist(&db).map_err(|e| format!("{:?}", e))
+	}
+
+	/// Reads the state of the accumulator at the given block.
+	fn state_at(&self, hash: H256) -> std::result::Result<U2048, String> {
+		self.client.runtime_api().get_state(&BlockId::Hash(hash)).map_err(|e| format!("{:?}", e))
+	}
+
+	/// Writes the database to disk.
+	fn persist(&self, db: &WitnessDb) -> io::Result<()> {
+		let data = serde_json::to_vec(db).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+		let tmp_path = self.path.with_extension("tmp");
+		fs::write(&tmp_path, data)?;
+		fs::rename(&tmp_path, &self.path)
+	}
+
+	/// Registers a UTXO. If the UTXO is already contained in the accumulator, its witness at the last
+	/// processed block must be provided. Otherwise, the witness is created once the UTXO has been added.
+	/// If no block has been processed yet, the service starts tracking from the last finalized block.
+	pub fn register(&self, utxo: UTXO, witness: Option<U2048>) -> std::result::Result<(), &'static str> {
+		let elem = subroutines::hash_to_prime(&utxo.encode());
+		let mut db = self.db.lock();
+
+		let state = match db.best {
+			Some((_, state)) => state,
+			None => {
+				let hash = self.client.info().chain.finalized_hash;
+				let state = self.state_at(hash).map_err(|_| "Failed to read the state of the accumulator.")?;
+				db.best = Some((hash, state));
+				state
+			},
+		};
+		if let Some(witness) = witness {
+			if !witnesses::verify_mem_wit(state, witness, elem) {
+				return Err("Witness is invalid.");
+			}
+		}
+
+		db.coins.retain(|coin| coin.utxo != utxo);
+		db.coins.push(TrackedCoin { utxo, elem, witness });
+		info!("Witness service registered UTXO {:?}", utxo);
+		self.persist(&db).map_err(|_| "Failed to persist the witness database.")
+	}
+
+	/// Stops maintaining the witness of a UTXO.
+	pub fn unregister(&self, utxo: UTXO) -> std::result::Result<bool, &'static str> {
+		let mut db = self.db.lock();
+		let len = db.coins.len();
+		db.coins.retain(|coin| coin.utxo != utxo);
+		let removed = db.coins.len() != len;
+		self.persist(&db).map_err(|_| "Failed to persist the witness database.")?;
+		Ok(removed)
+	}
+
+	/// Returns the latest witness of a UTXO along with the block it is valid at.
+	pub fn witness(&self, utxo: UTXO) -> Option<(H256, U2048)> {
+		let db = self.db.lock();
+		let (hash, _) = db.best?;
+		db.coins.iter()
+			.find(|coin| coin.utxo == utxo)
+			.and_then(|coin| coin.witness)
+			.map(|witness| (hash, witness))
+	}
+}
+
+/// Witness service RPC methods.
+#[rpc]
+pub trait WitnessServiceApi {
+	/// Registers a UTXO. The current witness must be supplied if the UTXO already exists.
+	#[rpc(name = "witness_register")]
+	fn register(&self, utxo: UTXO, witness: Option<U2048>) -> Result<()>;
+
+	/// Stops maintaining the witness of a UTXO.
+	#[rpc(name = "witness_unregister")]
+	fn unregister(&self, utxo: UTXO) -> Result<bool>;
+
+	/// Returns the hash of the last finalized block processed along with the witness of the UTXO.
+	#[rpc(name = "witness_get")]
+	fn get_witness(&self, utxo: UTXO) -> Result<Option<(H256, U2048)>>;
+}
+
+/// Converts a service error into an RPC error.
+fn rpc_error(message: &str) -> Error {
+	Error {
+		code: ErrorCode::InvalidParams,
+		message: message.into(),
+		data: None,
+	}
+}
+
+impl<B, E, RA> WitnessServiceApi for Arc<WitnessService<B, E, RA>> where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: StatelessApi<Block>,
+{
+	fn register(&self, utxo: UTXO, witness: Option<U2048>) -> Result<()> {
+		WitnessService::register(self, utxo, witness).map_err(rpc_error)
+	}
+
+	fn unregister(&self, utxo: UTXO) -> Result<bool> {
+		WitnessService::unregister(self, utxo).map_err(rpc_error)
+	}
+
+	fn get_witness(&self, utxo: UTXO) -> Result<Option<(H256, U2048)>> {
+		Ok(self.witness(utxo))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use stateless_blockchain_runtime::stateless::Lock;
+
+	// Elements are small primes that are coprime to the order of the test group, so that the
+	// witnesses are unique.
+	fn coin(id: u64, elem: u64, witness: Option<U2048>) -> TrackedCoin {
+		let utxo = UTXO { pub_key: H256::from_low_u64_be(id), id, lock: Lock::default() };
+		TrackedCoin { utxo, elem: U2048::from(elem), witness }
+	}
+
+	fn delta(deletion: Option<(U2048, U2048, U2048)>, addition: Option<(U2048, U2048, U2048)>) -> BlockDelta {
+		BlockDelta { block_hash: H256::zero(), deletion, addition }
+	}
+
+	fn pow(base: U2048, exp: u64) -> U2048 {
+		subroutines::mod_exp(base, U2048::from(exp), U2048::from_dec_str(MODULUS).unwrap())
+	}
+
+	#[test]
+	fn test_apply_addition() {
+		let generator = U2048::from(2);
+		let mut db = WitnessDb { best: None, coins: vec![coin(0, 5, None), coin(1, 7, None)] };
+
+		// 5 is added along with the untracked 11 while 7 is still pending.
+		let addition = accumulator::batch_add(generator, &vec![U2048::from(5), U2048::from(11)]);
+		let state = db.apply_delta(generator, &delta(None, Some(addition)));
+		assert_eq!(state, addition.0);
+		assert_eq!(db.coins[0].witness, Some(pow(generator, 11)));
+		assert_eq!(witnesses::verify_mem_wit(state, db.coins[0].witness.unwrap(), U2048::from(5)), true);
+		assert_eq!(db.coins[1].witness, None);
+
+		// Empty blocks do not change anything.
+		let before = db.clone();
+		assert_eq!(db.apply_delta(state, &delta(None, None)), state);
+		assert_eq!(db, before);
+	}
+
+	#[test]
+	fn test_apply_deletion() {
+		let generator = U2048::from(2);
+		let elems = vec![U2048::from(5), U2048::from(7), U2048::from(11)];
+		let (state, _, _) = accumulator::batch_add(generator, &elems);
+		let all = witnesses::create_all_mem_wit(generator, &elems);
+		let mut db = WitnessDb { best: None, coins: vec![coin(0, 5, Some(all[0])), coin(1, 7, Some(all[1]))] };
+
+		// The untracked 11 is deleted.
+		let deletion = accumulator::batch_delete(state, &vec![(elems[2], all[2])]);
+		let new_state = db.apply_delta(state, &delta(Some(deletion), None));
+		assert_eq!(new_state, deletion.0);
+		assert_eq!(db.coins.len(), 2);
+		assert_eq!(db.coins[0].witness, Some(pow(generator, 7)));
+		assert_eq!(db.coins[1].witness, Some(pow(generator, 5)));
+		for coin in db.coins.iter() {
+			assert_eq!(witnesses::verify_mem_wit(new_state, coin.witness.unwrap(), coin.elem), true);
+		}
+	}
+
+	#[test]
+	fn test_apply_spend() {
+		let generator = U2048::from(2);
+		let elems = vec![U2048::from(5), U2048::from(7)];
+		let (state, _, _) = accumulator::batch_add(generator, &elems);
+		let all = witnesses::create_all_mem_wit(generator, &elems);
+		// 5 is tracked twice: once as an accumulated coin and once as a pending coin of another UTXO.
+		let mut db = WitnessDb {
+			best: None,
+			coins: vec![coin(0, 5, Some(all[0])), coin(1, 7, Some(all[1])), coin(2, 5, None)],
+		};
+
+		// 5 is spent and the untracked 11 is created in the same block.
+		let deletion = accumulator::batch_delete(state, &vec![(elems[0], all[0])]);
+		let addition = accumulator::batch_add(deletion.0, &vec![U2048::from(11)]);
+		let new_state = db.apply_delta(state, &delta(Some(deletion), Some(addition)));
+		assert_eq!(new_state, addition.0);
+
+		// The spent coin is dropped while the pending coin is kept.
+		assert_eq!(db.coins.iter().map(|coin| coin.utxo.id).collect::<Vec<_>>(), vec![1, 2]);
+		assert_eq!(db.coins[0].witness, Some(pow(generator, 11)));
+		assert_eq!(witnesses::verify_mem_wit(new_state, db.coins[0].witness.unwrap(), U2048::from(7)), true);
+		assert_eq!(db.coins[1].witness, None);
+	}
+
+	#[test]
+	fn test_apply_invalid_witness() {
+		let generator = U2048::from(2);
+		let elems = vec![U2048::from(5), U2048::from(7), U2048::from(11)];
+		let (state, _, _) = accumulator::batch_add(generator, &elems);
+		let all = witnesses::create_all_mem_wit(generator, &elems);
+		// The witness of 7 is not valid, so it cannot be updated.
+		let mut db = WitnessDb { best: None, coins: vec![coin(0, 5, Some(all[0])), coin(1, 7, Some(U2048::from(3)))] };
+		assert_eq!(witnesses::verify_mem_wit(state, U2048::from(3), elems[1]), false);
+
+		// The invalid coin is dropped while the other coin is still followed.
+		let deletion = accumulator::batch_delete(state, &vec![(elems[2], all[2])]);
+		let new_state = db.apply_delta(state, &delta(Some(deletion), None));
+		assert_eq!(db.coins.iter().map(|coin| coin.utxo.id).collect::<Vec<_>>(), vec![0]);
+		assert_eq!(witnesses::verify_mem_wit(new_state, db.coins[0].witness.unwrap(), U2048::from(5)), true);
+	}
+}
@@ -6,37 +6,83 @@ use accumulator::witnesses;
 use rstd::prelude::Vec;
 use runtime_io;
 
+/// Position of a bit within the vector. Indices are hashed as fixed-width integers so that their
+/// prime representations do not depend on the platform(e.g. the 32-bit WASM runtime).
+pub type Index = u128;
+
+/// Hashes an index to its prime representation. Indices that fit into 64 bits are hashed as 8 bytes
+/// and larger ones as 16 bytes, which keeps the encoding injective while the primes of the smaller
+/// indices stay the same as those of the 64-bit "usize" indices used originally.
+pub fn index_to_prime(index: Index) -> U2048 {
+    let bytes = index.to_le_bytes();
+    if index <= u64::max_value() as Index {
+        return subroutines::hash_to_prime(&bytes[..8]);
+    }
+    return subroutines::hash_to_prime(&bytes);
+}
+
+/// Checks that every bit has exactly one index and that no index occurs more than once. Otherwise,
+/// a single position could be committed or opened to both 0 and 1.
+pub fn validate(b: &[bool], i: &[Index]) -> bool {
+    if b.len() != i.len() {
+        return false;
+    }
+    let mut sorted = i.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    return sorted.len() == i.len();
+}
+
+/// Checks that the group elements of a witness are reduced modulo the RSA modulus and nonzero. Otherwise,
+/// the same bits could be opened with several different witnesses.
+pub fn validate_witness(witness: Witness) -> bool {
+    let modulus = U2048::from_dec_str(MODULUS).unwrap();
+    let elem = match witness {
+        Witness::MemWit(witness) => witness,
+        Witness::NonMemWit((_, _, b)) => b,
+    };
+    return elem > U2048::from(0) && elem < modulus;
+}
+
 /// Commit a vector of bits(represented as bool array) to an accumulator. The second value of
-/// the returned tuple is the product of the accumulated elements.
+/// the returned tuple is the product of the accumulated elements. Returns None if the input is
+/// invalid(see "validate").
 /// NOTE: In the stateless blockchain model, after the validator commits the vector to the accumulator,
 /// users should immediately request membership witnesses for their committed bit using the returned "product" value.
-pub fn commit(accumulator: U2048, values: &[bool], indices: &[usize]) -> (U2048, U2048) {
+pub fn commit(accumulator: U2048, values: &[bool], indices: &[Index]) -> Option<(U2048, U2048)> {
+    if !validate(values, indices) {
+        return None;
+    }
     let elems: Vec<U2048> = values
         .into_iter()
         .enumerate()
         .filter(|(_, val)| **val)
-        .map(|(index, _)| subroutines::hash_to_prime(&indices[index].to_le_bytes()))
+        .map(|(index, _)| index_to_prime(indices[index]))
         .collect();
     let (state, product, _) = batch_add(accumulator, &elems);
-    return (state, product);
+    return Some((state, product));
 }
 
 /// Create an opening for a bit commitment. The current state of the accumulator should equal
 /// "old_state" raised to the "agg" power(product of aggregated elements) where the committed bit
-/// is contained in "agg".
-pub fn open(old_state: U2048, bit: bool, index: usize, agg: U2048) -> Witness {
-    let elem = subroutines::hash_to_prime(&index.to_le_bytes());
+/// is contained in "agg". Returns None if the bit is not committed in "agg".
+pub fn open(old_state: U2048, bit: bool, index: Index, agg: U2048) -> Option<Witness> {
+    let (pi_i, pi_e) = batch_open(old_state, agg, &[bit], &[index])?;
     if bit {
-        return Witness::MemWit(witnesses::mem_wit_create(old_state, agg, elem).unwrap());
+        return Some(pi_i);
     }
     else {
-        return Witness::NonMemWit(witnesses::non_mem_wit_create(old_state, agg, elem));
+        return Some(pi_e);
     }
 }
 
 /// Verify a membership/non-membership proof (produced by an opening) for a given bit commitment.
-pub fn verify(old_state: U2048, accumulator: U2048, bit: bool, index: usize, proof: Witness) -> bool {
-    let elem = subroutines::hash_to_prime(&index.to_le_bytes());
+/// Returns false if the proof is invalid(see "validate_witness").
+pub fn verify(old_state: U2048, accumulator: U2048, bit: bool, index: Index, proof: Witness) -> bool {
+    if !validate_witness(proof) {
+        return false;
+    }
+    let elem = index_to_prime(index);
     if bit {
         match proof {
             Witness::MemWit(witness) => {
@@ -60,8 +106,8 @@ pub fn verify(old_state: U2048, accumulator: U2048, bit: bool, index: usize, pro
 }
 
 /// Given a bit array and an array of corresponding indices, outputs the product of the "ones"
-/// elements and the product of the "zeros" elements.
-pub fn get_bit_elems(b: &[bool], i: &[usize]) -> (U2048, U2048) {
+/// elements and the product of the "zeros" elements. Assumes that the input is valid(see "validate").
+pub fn get_bit_elems(b: &[bool], i: &[Index]) -> (U2048, U2048) {
     let ones_indices: Vec<usize> = b
         .into_iter()
         .enumerate()
@@ -79,13 +125,13 @@ pub fn get_bit_elems(b: &[bool], i: &[usize]) -> (U2048, U2048) {
     let ones: Vec<U2048> = ones_indices
         .into_iter()
         .enumerate()
-        .map(|(_, index)| subroutines::hash_to_prime(&(i[index]).to_le_bytes()))
+        .map(|(_, index)| index_to_prime(i[index]))
         .collect();
 
     let zeros: Vec<U2048> = zeros_indices
         .into_iter()
         .enumerate()
-        .map(|(_, index)| subroutines::hash_to_prime(&(i[index]).to_le_bytes()))
+        .map(|(_, index)| index_to_prime(i[index]))
         .collect();
 
     let p_ones = subroutines::prime_product(&ones);
@@ -95,19 +141,32 @@ pub fn get_bit_elems(b: &[bool], i: &[usize]) -> (U2048, U2048) {
 
 /// Batch opens a set of bit commitments. The accumulated values of the commitments must be contained in
 /// the inputted aggregated value(agg) and the current state of the accumulator must equal old_state^agg.
+/// Returns None if the input is invalid or if the bits are not committed in "agg", i.e. a "one" bit is
+/// not contained in "agg" or a "zero" bit is.
 /// This function has been slightly modified from the original specification. See page 20 of the paper for more info.
-pub fn batch_open(old_state: U2048, agg: U2048, b: &[bool], i: &[usize]) -> (Witness, Witness) {
+pub fn batch_open(old_state: U2048, agg: U2048, b: &[bool], i: &[Index]) -> Option<(Witness, Witness)> {
+    if !validate(b, i) {
+        return None;
+    }
     let (p_ones, p_zeros) = get_bit_elems(b, i);
 
-    let pi_inclusion = Witness::MemWit(witnesses::mem_wit_create(old_state, agg, p_ones).unwrap());
+    // A non-membership witness only exists if none of the "zeros" elements are contained in "agg"
+    if subroutines::bezout(agg, p_zeros).is_none() {
+        return None;
+    }
+    let pi_inclusion = Witness::MemWit(witnesses::mem_wit_create(old_state, agg, p_ones)?);
     let pi_exclusion = Witness::NonMemWit(witnesses::non_mem_wit_create(old_state, agg, p_zeros));
 
-    return (pi_inclusion, pi_exclusion);
+    return Some((pi_inclusion, pi_exclusion));
 }
 
-/// Verifies a set of membership and non-membership witnesses for a set of bit commitments.
+/// Verifies a set of membership and non-membership witnesses for a set of bit commitments. Returns false
+/// if the input or the witnesses are invalid(see "validate" and "validate_witness").
 /// This function has been slightly modified from the original specification. See page 20 of the paper for more info.
-pub fn batch_verify(old_state: U2048, accumulator: U2048, b: &[bool], i: &[usize], pi_i: Witness, pi_e: Witness) -> bool {
+pub fn batch_verify(old_state: U2048, accumulator: U2048, b: &[bool], i: &[Index], pi_i: Witness, pi_e: Witness) -> bool {
+    if !validate(b, i) || !validate_witness(pi_i) || !validate_witness(pi_e) {
+        return false;
+    }
     let (p_ones, p_zeros) = get_bit_elems(b, i);
 
     let ver_mem_result;
@@ -133,6 +192,7 @@ pub fn batch_verify(old_state: U2048, accumulator: U2048, b: &[bool], i: &[usize
 }
 
 /// Updates a segment of a vector commitment. Assumes that an honest party performs the update.
+/// Returns None if the input is invalid or if a bit that is set to 0 is not contained in "agg".
 /// Arguments:
 /// - accumulator: The current state of the accumulator.
 /// - old_state: A previous state.
@@ -140,17 +200,20 @@ pub fn batch_verify(old_state: U2048, accumulator: U2048, b: &[bool], i: &[usize
 ///        bits should be contained in this value.
 /// - b: New bit array.
 /// - i: Affected indices.
-pub fn update(accumulator: U2048, old_state: U2048, agg: U2048, b: &[bool], i: &[usize]) -> U2048 {
+pub fn update(accumulator: U2048, old_state: U2048, agg: U2048, b: &[bool], i: &[Index]) -> Option<U2048> {
+    if !validate(b, i) {
+        return None;
+    }
     let (p_ones, p_zeros) = get_bit_elems(b, i);
 
     // Delete p_zeros elements
-    let mem_wit = witnesses::mem_wit_create(old_state, agg, p_zeros).unwrap();
-    let mut new_state = delete(accumulator, p_zeros, mem_wit).unwrap();
+    let mem_wit = witnesses::mem_wit_create(old_state, agg, p_zeros)?;
+    let mut new_state = delete(accumulator, p_zeros, mem_wit)?;
 
     // Add p_ones elements
     new_state = add(new_state, p_ones);
 
-    return new_state;
+    return Some(new_state);
 }
 
 #[cfg(test)]
@@ -162,16 +225,16 @@ mod tests {
         // Commit vector
         let accumulator = U2048::from(2);
         let arr: [bool; 3] = [true, false, true];
-        let (state, product) = commit(accumulator, &arr, &[0, 1, 2]);
+        let (state, product) = commit(accumulator, &arr, &[0, 1, 2]).unwrap();
 
         // Check commit
-        let h_0 = subroutines::hash_to_prime(&(0 as usize).to_le_bytes());
-        let h_2 = subroutines::hash_to_prime(&(2 as usize).to_le_bytes());
+        let h_0 = index_to_prime(0);
+        let h_2 = index_to_prime(2);
         assert_eq!(subroutines::mod_exp(accumulator, h_0*h_2, U2048::from_dec_str(MODULUS).unwrap()), state);
 
         // Open at two indices
-        let open_1 = open(U2048::from(2), false, 1, product);
-        let open_2 = open(U2048::from(2), true, 2, product);
+        let open_1 = open(U2048::from(2), false, 1, product).unwrap();
+        let open_2 = open(U2048::from(2), true, 2, product).unwrap();
 
         // Verify
         assert_eq!(verify(accumulator, state, false, 1, open_1), true);
@@ -188,9 +251,9 @@ mod tests {
         let arr: [bool; 3] = [false, false, true];
         let indices = [0, 1, 5];
 
-        let h_0 = subroutines::hash_to_prime(&(0 as usize).to_le_bytes());
-        let h_1 = subroutines::hash_to_prime(&(1 as usize).to_le_bytes());
-        let h_5 = subroutines::hash_to_prime(&(5 as usize).to_le_bytes());
+        let h_0 = index_to_prime(0);
+        let h_1 = index_to_prime(1);
+        let h_5 = index_to_prime(5);
 
         let (p_ones, p_zeros) = get_bit_elems(&arr, &indices);
         assert_eq!(p_ones, h_5);
@@ -201,14 +264,14 @@ mod tests {
     fn test_batch_open_and_verify() {
         let accumulator = U2048::from(2);
         let arr: [bool; 6] = [true, false, true, false, false, true];
-        let (state, product) = commit(accumulator, &arr, &[0, 1, 2, 3, 4, 5]);
+        let (state, product) = commit(accumulator, &arr, &[0, 1, 2, 3, 4, 5]).unwrap();
 
-        let (i, e) = batch_open(accumulator, product, &[true, false, false, true], &[0, 3, 4, 5]);
+        let (i, e) = batch_open(accumulator, product, &[true, false, false, true], &[0, 3, 4, 5]).unwrap();
 
-        let h_0 = subroutines::hash_to_prime(&(0 as usize).to_le_bytes());
-        let h_3 = subroutines::hash_to_prime(&(3 as usize).to_le_bytes());
-        let h_4 = subroutines::hash_to_prime(&(4 as usize).to_le_bytes());
-        let h_5 = subroutines::hash_to_prime(&(5 as usize).to_le_bytes());
+        let h_0 = index_to_prime(0);
+        let h_3 = index_to_prime(3);
+        let h_4 = index_to_prime(4);
+        let h_5 = index_to_prime(5);
 
         // Manual check of openings
         let ones_product = subroutines::prime_product(&vec![h_0, h_5]);
@@ -239,15 +302,112 @@ mod tests {
     fn test_update() {
         let accumulator = U2048::from(2);
         let arr: [bool; 6] = [true, false, true, false, false, true];
-        let (state, product) = commit(accumulator, &arr, &[0, 1, 2, 3, 4, 5]);
+        let (state, product) = commit(accumulator, &arr, &[0, 1, 2, 3, 4, 5]).unwrap();
 
-        let h_0 = subroutines::hash_to_prime(&(0 as usize).to_le_bytes());
-        let h_3 = subroutines::hash_to_prime(&(3 as usize).to_le_bytes());
-        let h_4 = subroutines::hash_to_prime(&(4 as usize).to_le_bytes());
+        let h_0 = index_to_prime(0);
+        let h_3 = index_to_prime(3);
+        let h_4 = index_to_prime(4);
 
-        // Missing: checking that inputs are valid
-        let new_state = update(state, accumulator, product, &[false, true, true, false], &[2, 3, 4, 5]);
+        let new_state = update(state, accumulator, product, &[false, true, true, false], &[2, 3, 4, 5]).unwrap();
         assert_eq!(new_state, subroutines::mod_exp(accumulator, h_0 * h_3 * h_4, U2048::from_dec_str(MODULUS).unwrap()));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate(&[true, false], &[0, 1]), true);
+        assert_eq!(validate(&[], &[]), true);
+        assert_eq!(validate(&[true, false], &[0]), false);
+        assert_eq!(validate(&[true], &[0, 1]), false);
+        assert_eq!(validate(&[true, false, true], &[0, 1, 0]), false);
+
+        // Invalid input is rejected instead of panicking
+        let accumulator = U2048::from(2);
+        assert_eq!(commit(accumulator, &[true, true], &[0]), None);
+        assert_eq!(commit(accumulator, &[true, false], &[0, 0]), None);
+        let (state, product) = commit(accumulator, &[true, false], &[0, 1]).unwrap();
+        assert_eq!(batch_open(accumulator, product, &[true], &[0, 1]), None);
+        assert_eq!(update(state, accumulator, product, &[false, true], &[0]), None);
+
+        // Witnesses that are not reduced or zero are rejected
+        let modulus = U2048::from_dec_str(MODULUS).unwrap();
+        let (pi_i, pi_e) = batch_open(accumulator, product, &[true, false], &[0, 1]).unwrap();
+        assert_eq!(batch_verify(accumulator, state, &[true, false], &[0, 1], pi_i, pi_e), true);
+        if let (Witness::MemWit(mem_wit), Witness::NonMemWit((a, sign_a, b))) = (pi_i, pi_e) {
+            assert_eq!(verify(accumulator, state, true, 0, Witness::MemWit(mem_wit + modulus)), false);
+            assert_eq!(verify(accumulator, state, true, 0, Witness::MemWit(U2048::from(0))), false);
+            assert_eq!(verify(accumulator, state, false, 1, Witness::NonMemWit((a, sign_a, b + modulus))), false);
+            assert_eq!(batch_verify(accumulator, state, &[true, false], &[0, 1], Witness::MemWit(mem_wit + modulus), pi_e), false);
+        }
+    }
+
+    /// Tries to open the same index to both 0 and 1.
+    /// NOTE: With the toy modulus, a forged witness can be accepted by coincidence. Therefore, these
+    /// checks only assert that an opening of the wrong bit cannot be created and that openings that
+    /// claim both bits for one index are rejected before any witness is checked. Forged witnesses are
+    /// verified by "test_forged_position" with the "rsa-768" feature.
+    #[test]
+    fn test_position_binding() {
+        let accumulator = U2048::from(2);
+        let (state, product) = commit(accumulator, &[true, false], &[0, 1]).unwrap();
+
+        // The committed bits can be opened
+        let (pi_i, pi_e) = batch_open(accumulator, product, &[true, false], &[0, 1]).unwrap();
+        assert_eq!(batch_verify(accumulator, state, &[true, false], &[0, 1], pi_i, pi_e), true);
+
+        // The opposite bits cannot be opened
+        assert_eq!(open(accumulator, false, 0, product), None);
+        assert_eq!(open(accumulator, true, 1, product), None);
+        assert_eq!(batch_open(accumulator, product, &[false, false], &[0, 1]), None);
+
+        // An index cannot be opened to both bits at once
+        assert_eq!(batch_open(accumulator, product, &[true, false], &[0, 0]), None);
+        let (open_0, _) = batch_open(accumulator, product, &[true], &[0]).unwrap();
+        let (_, open_1) = batch_open(accumulator, product, &[false], &[1]).unwrap();
+        assert_eq!(batch_verify(accumulator, state, &[true, false], &[0, 0], open_0, open_1), false);
+        assert_eq!(batch_verify(accumulator, state, &[true], &[0, 0], open_0, open_1), false);
+    }
+
+    /// Forges witnesses that open the same index to both 0 and 1. The toy modulus only has 12 distinct
+    /// exponents, so their rejection is only meaningful and therefore only tested with the realistic
+    /// modulus of the "rsa-768" feature.
+    #[test]
+    #[cfg(feature = "rsa-768")]
+    fn test_forged_position() {
+        let accumulator = U2048::from(2);
+        let (state, product) = commit(accumulator, &[true, false], &[0, 1]).unwrap();
+        let h_0 = index_to_prime(0);
+        let h_1 = index_to_prime(1);
+
+        // Index 0 is opened to 1
+        let (pi_i, pi_e) = batch_open(accumulator, product, &[true, false], &[0, 1]).unwrap();
+        assert_eq!(batch_verify(accumulator, state, &[true, false], &[0, 1], pi_i, pi_e), true);
+        assert_eq!(verify(accumulator, state, true, 0, pi_i), true);
+
+        // A non-membership witness for index 0 that is created as if index 1 had been committed instead.
+        // Since no zero bits are opened as ones, the membership witness of the empty product is the state.
+        let forged_pi_i = Witness::MemWit(state);
+        let forged_pi_e = Witness::NonMemWit(witnesses::non_mem_wit_create(accumulator, h_1, h_0));
+        assert_eq!(witnesses::verify_mem_wit(state, state, U2048::from(1)), true);
+        assert_eq!(batch_verify(accumulator, state, &[false], &[0], forged_pi_i, forged_pi_e), false);
+        assert_eq!(verify(accumulator, state, false, 0, forged_pi_e), false);
+
+        // A non-membership witness for index 0 that is referenced from the current state instead of the
+        // generator, i.e. it only proves that index 0 has not been added since.
+        let forged_pi_e = Witness::NonMemWit(witnesses::non_mem_wit_create(state, U2048::from(1), h_0));
+        if let Witness::NonMemWit(non_mem_wit) = forged_pi_e {
+            assert_eq!(witnesses::verify_non_mem_wit(state, state, non_mem_wit, h_0), true);
+        }
+        assert_eq!(batch_verify(accumulator, state, &[false], &[0], forged_pi_i, forged_pi_e), false);
+        assert_eq!(verify(accumulator, state, false, 0, forged_pi_e), false);
+
+        // The non-membership witness of index 1 is not valid for index 0 either
+        assert_eq!(batch_verify(accumulator, state, &[false], &[0], forged_pi_i, pi_e), false);
+        assert_eq!(verify(accumulator, state, false, 0, pi_e), false);
+
+        // Index 1 cannot be opened to 1 with the membership witness of a product that includes it
+        let forged_pi_i = Witness::MemWit(witnesses::mem_wit_create(accumulator, product * h_1, h_1).unwrap());
+        let empty_pi_e = Witness::NonMemWit(witnesses::non_mem_wit_create(accumulator, product, U2048::from(1)));
+        assert_eq!(verify(accumulator, state, true, 1, forged_pi_i), false);
+        assert_eq!(batch_verify(accumulator, state, &[true, true], &[0, 1], forged_pi_i, empty_pi_e), false);
+    }
+}
@@ -3,31 +3,100 @@
 /// Account-Based Stateless Blockchain
 /// ***DISCLOSURE*** This module is incomplete, untested, and completely experimental.
 
-use support::{decl_module, decl_storage, decl_event, ensure, dispatch::Result, StorageValue, traits::Get};
+use support::{decl_module, decl_storage, decl_event, ensure, dispatch::Result, StorageValue, StorageMap, traits::Get};
 use system::ensure_signed;
+use primitives::{H256, H512, sr25519};
+use sr_primitives::traits::{Verify, SaturatedConversion};
+use rstd::prelude::Vec;
 use codec::{Encode, Decode};
 use accumulator::*;
 pub mod binary;
 pub mod vc;
 
+pub use vc::Key;
+
+pub type Balance = u32;
+pub type Nonce = u32;
+
+/// The value committed at each key. The nonce is incremented by every debit of the key, so a signed
+/// debit cannot be replayed once it has been applied.
+/// NOTE: The balance and the nonce share 64 bit indices, since the product of the prime
+/// representations of all bits of a value must fit into a U2048.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Default, Clone, Copy, Encode, Decode, PartialEq, Eq)]
+pub struct Account {
+    pub balance: Balance,
+    pub nonce: Nonce,
+}
+
+/// The balance occupies the lower 32 bit indices of a key and the nonce the upper 32.
+impl vc::Value for Account {
+    const BITS: usize = 64;
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = self.balance.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        return bytes;
+    }
+}
+
+/// Opening of the value committed at a key. A value is opened against the checkpoint of the block
+/// that wrote it(see "verify_opening"), while the opening of an empty key proves that none of its bit
+/// indices are contained in the accumulator and ignores the block.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Default, Clone, Copy, Encode, Decode, PartialEq, Eq)]
+pub struct Opening {
+    /// The block that last wrote the key.
+    pub block: u64,
+    /// Membership witness of the element written by the block in the current state.
+    pub pi_i: Witness,
+    /// Non-membership witness of the zero bits of the value.
+    pub pi_e: Witness,
+}
+
 #[cfg_attr(feature = "std", derive(Debug))]
 #[derive(Default, Clone, Encode, Decode, PartialEq, Eq)]
 pub struct Transaction {
-    sender_key: u8,
-    sender_balance: u8,
-    sender_elem: U2048,
-    sender_opening: (Witness, Witness),
-    receiver_key: u8,
-    receiver_balance: u8,
-    receiver_elem: U2048,
-    receiver_opening: (Witness, Witness),
-    amount: u8,
+    sender_key: Key,
+    /// The public key the sender key is derived from(see "account_key").
+    sender_pub_key: H256,
+    /// The balance committed at the sender key.
+    sender_balance: Balance,
+    /// The nonce of this debit. Equals the committed nonce for the first debit of the key in a block
+    /// and is incremented by every further debit of the key in the same block.
+    sender_nonce: Nonce,
+    sender_opening: Opening,
+    receiver_key: Key,
+    /// The value committed at the receiver key.
+    receiver_balance: Balance,
+    receiver_nonce: Nonce,
+    receiver_opening: Opening,
+    amount: Balance,
+    /// sr25519 signature of the sender over the "signing_payload".
+    signature: H512,
+}
+
+impl Transaction {
+    /// Returns the payload that must be signed by the sender. It includes the nonce of the sender,
+    /// which makes the signature valid for a single transfer only.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        return (self.sender_key, self.sender_nonce, self.receiver_key, self.amount).encode();
+    }
+}
+
+/// Verifies an sr25519 signature of the given public key.
+pub fn verify_signature(signature: &H512, pub_key: &H256, payload: &[u8]) -> bool {
+    let signature = sr25519::Signature::from_raw(signature.to_fixed_bytes());
+    return signature.verify(payload, &sr25519::Public::from_raw(pub_key.to_fixed_bytes()));
 }
 
 /// The module's configuration trait.
 pub trait Trait: system::Trait {
     type Event: From<Event> + Into<<Self as system::Trait>::Event>;
-    type KeySpace: Get<u8>;
+    /// Keys must be smaller than this value. If it is 0, every key is rejected(see "account_key").
+    type KeySpace: Get<Key>;
+    /// Number of recent blocks whose checkpoints are kept. Values that have been written by an older
+    /// block are opened against the generator instead(see "verify_opening").
+    type CheckpointWindow: Get<u64>;
 }
 
 // This module's storage items.
@@ -35,13 +104,17 @@ decl_storage! {
     trait Store for Module<T: Trait> as StatelessAccounts {
         State get(get_state): U2048 = U2048::from(2);  // Use 2 as an arbitrary generator with "unknown" order.
         WitnessData get(get_witness_data): Vec<(U2048, U2048)>;
-        NewKeyValuePairs: Vec<(u8, u8)>;
+        /// The value committed at each key that is updated in this block and its updated value.
+        Updates get(get_updates): Vec<(Key, Account, Account)>;
+        /// The state after the deletions of each recent block that writes a value and the state after
+        /// its additions. Values are opened against the checkpoint of the block that wrote them.
+        Checkpoints get(checkpoint): map u64 => Option<(U2048, U2048)>;
     }
 }
 
 decl_event!(
     pub enum Event {
-        TokensMinted(U2048, U2048),
+        TokensMinted(Key, Balance),
         Deletion(U2048, U2048, U2048),
         Addition(U2048, U2048, U2048),
     }
@@ -51,83 +124,254 @@ decl_module! {
     /// The module declaration.
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn deposit_event() = default;
-        const KeySpace: u8 = T::KeySpace::get();
-
-        /// Arbitrarily add a new key-value store to the accumulator.
-        /// NOTE: The key must not exist initially.
-        pub fn mint(origin, key: u8, amount: u8) -> Result {
+        const KeySpace: Key = T::KeySpace::get();
+        const CheckpointWindow: u64 = T::CheckpointWindow::get();
+
+        /// Arbitrarily add a new key-value store to the accumulator. Like any other update, the new
+        /// value is added when the block is finalized.
+        /// NOTE: The key must not exist initially. This is proven by a non-membership proof for all
+        /// bit indices of the key, which is referenced from the generator of the accumulator.
+        pub fn mint(origin, key: Key, amount: Balance, proof: Witness) -> Result {
             ensure_signed(origin)?;
-            let (state, product) = vc::commit(State::get(), &[key as usize], &[amount]);
-            State::put(state);
-            Self::deposit_event(Event::TokensMinted(state, product));
+            ensure!(key < T::KeySpace::get(), "Key is outside of the key space.");
+            ensure!(!Self::is_pending(key), "Key is updated in this block.");
+            ensure!(vc::verify_empty_key::<Account>(Self::generator(), State::get(), key, proof), "Key is not empty.");
+
+            Self::store(&[(key, Account::default(), Account { balance: amount, nonce: 0 }, Opening::default())])?;
+            Self::deposit_event(Event::TokensMinted(key, amount));
             Ok(())
         }
 
-        /// Submit a transaction to the chain.
-        /// NOTE: All transactions must be referenced from the same previous "state". In practice,
-        /// this might be the state of the previous block for example. This is a workaround to
-        /// prevent having to pass in the product of all of the elements in the accumulator.
-        pub fn add_transaction(origin, transaction: Transaction, old_state: U2048) -> Result {
+        /// Submit a transaction to the chain. The transaction must be signed by the public key that the
+        /// sender key is derived from. Transfers that touch a key which has already been updated in
+        /// this block are applied on top of its updated value.
+        /// NOTE: The state is only updated when the block is finalized, so all openings of a block are
+        /// verified against the state before the block. Each opening names the block that last wrote
+        /// the key, so it only depends on the elements added by that block(see "verify_opening").
+        pub fn add_transaction(origin, transaction: Transaction) -> Result {
             ensure_signed(origin)?;
-            // Get the opening of the sender
-            let (pi_i_sender, pi_e_sender) = transaction.sender_opening;
+            ensure!(transaction.sender_key != transaction.receiver_key, "Sender and receiver keys must differ.");
+            ensure!(Self::account_key(&transaction.sender_pub_key) == Some(transaction.sender_key), "Key does not belong to the signer.");
+            ensure!(verify_signature(&transaction.signature, &transaction.sender_pub_key, &transaction.signing_payload()),
+                    "Signature is invalid.");
+
+            // Get the committed and the updated values of both keys
+            let sender = Account { balance: transaction.sender_balance, nonce: transaction.sender_nonce };
+            let receiver = Account { balance: transaction.receiver_balance, nonce: transaction.receiver_nonce };
+            let (sender_committed, sender_pending) = Self::load(transaction.sender_key, sender, transaction.sender_opening)?;
+            let (receiver_committed, receiver_pending) = Self::load(transaction.receiver_key, receiver, transaction.receiver_opening)?;
+
+            // The signed nonce must be the next nonce of the sender, which prevents replays
+            ensure!(transaction.sender_nonce == sender_pending.nonce, "Nonce is invalid.");
+
+            // Ensure that the sender isn't spending more than balance and that the receiver balance fits
+            let sender_balance = sender_pending.balance.checked_sub(transaction.amount)
+                .ok_or("User is trying to spend more than balance.")?;
+            let receiver_balance = receiver_pending.balance.checked_add(transaction.amount)
+                .ok_or("Receiver balance is too large.")?;
+            let sender_nonce = sender_pending.nonce.checked_add(1).ok_or("Nonce is too large.")?;
+
+            // Temporarily store the membership proofs and the new key-value pairs to be processed later
+            Self::store(&[
+                (transaction.sender_key, sender_committed, Account { balance: sender_balance, nonce: sender_nonce },
+                 transaction.sender_opening),
+                (transaction.receiver_key, receiver_committed, Account { balance: receiver_balance, ..receiver_pending },
+                 transaction.receiver_opening),
+            ])?;
+            Ok(())
+        }
 
-            // Verify that it is valid
-            ensure!(vc::verify_at_key(old_state, State::get(), transaction.sender_key as usize,
-            transaction.sender_balance, pi_i_sender, pi_e_sender), "Opening is invalid.");
+        fn on_finalize(n: T::BlockNumber) {
+            let block = n.saturated_into::<u64>();
+            let mut state = State::get();
 
-            // Ensure that the sender isn't spending more than balance
-            ensure!(transaction.sender_balance >= transaction.amount, "User is trying to spend more than balance.");
+            // Remove previous key-value commitment.
+            if Self::get_witness_data().len() > 0 {
+                let (new_state, product, proof) = accumulator::batch_delete(state, &WitnessData::get());
+                Self::deposit_event(Event::Deletion(new_state, product, proof));
+                state = new_state;
+            }
 
-            // Verify receiver opening
-            let (pi_i_receiver, pi_e_receiver) = transaction.receiver_opening;
-            ensure!(vc::verify_at_key(old_state, State::get(), transaction.receiver_key as usize,
-                    transaction.receiver_balance, pi_i_receiver, pi_e_receiver), "Opening is invalid.");
+            // Get the integer representations of the new key-value pairs. Their product always fits
+            // into a U2048, since updates that would overflow it are rejected(see "store").
+            let elems = Self::write_elems(&Updates::get(), block).unwrap_or_default();
 
-            // Add membership proofs to temporary vector to be processed later
-            if let Witness::MemWit(sender_witness) = pi_i_sender {
-                WitnessData::append(&vec![(transaction.sender_elem, sender_witness)]);
+            // Add updated key-value pairs and record the checkpoint of the block.
+            if elems.len() > 0 {
+                let (new_state, product, proof) = accumulator::batch_add(state, &elems);
+                Self::deposit_event(Event::Addition(new_state, product, proof));
+                Checkpoints::insert(block, (state, new_state));
+                state = new_state;
             }
 
-            if let Witness::MemWit(receiver_witness) = pi_i_receiver {
-                WitnessData::append(&vec![(transaction.receiver_elem, receiver_witness)]);
+            // Update accumulator and prune the checkpoint that falls out of the window
+            State::put(state);
+            if let Some(pruned) = block.checked_sub(T::CheckpointWindow::get()) {
+                Checkpoints::remove(pruned);
             }
 
-            // Currently omitting non-membership proofs for simplicity
+            // Clear storage items
+            WitnessData::kill();
+            Updates::kill();
+        }
+    }
+}
 
-            // Temporarily store the new key-value pairs
-            NewKeyValuePairs::append(&vec![(transaction.sender_key, transaction.sender_balance-transaction.amount)]);
-            NewKeyValuePairs::append(&vec![(transaction.receiver_key, transaction.receiver_balance+transaction.amount)]);
-            Ok(())
+impl<T: Trait> Module<T> {
+    /// Returns the generator that the accumulator was instantiated with.
+    pub fn generator() -> U2048 {
+        return U2048::from(2);
+    }
+
+    /// Derives the key of an account by hashing its identifier(e.g. its public key) into the key space.
+    /// Returns `None` if the key space is empty, in which case no key can be used.
+    /// NOTE: Different identifiers can be hashed to the same key, and the owners of all of them can sign
+    /// for its balance. Finding an identifier for a given key takes about "KeySpace" hashes, so the key
+    /// space should cover all 64 bits of a key(as it does in the runtime).
+    pub fn account_key<A: Encode>(account: &A) -> Option<Key> {
+        let hash = runtime_io::blake2_256(&account.encode());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash[..8]);
+        return Key::from_le_bytes(bytes).checked_rem(T::KeySpace::get());
+    }
+
+    /// Returns the prime that marks the element written to a key by a block.
+    pub fn write_marker(key: Key, block: u64) -> U2048 {
+        return subroutines::hash_to_prime(&(&b"write"[..], key, block).encode());
+    }
+
+    /// Returns the element that is added when a block writes a value to a key, i.e. the product of the
+    /// prime representations of the one bits of the value and the write marker. Returns None if the
+    /// product does not fit into a U2048, which is only possible if almost all bits of the value are set.
+    pub fn write_elem(key: Key, value: Account, block: u64) -> Option<U2048> {
+        return vc::get_key_value_elem(key, value).checked_mul(Self::write_marker(key, block));
+    }
+
+    /// Returns the elements that a block adds given its updates. Empty values are not written, so that
+    /// an empty key never contains an element. Returns None if the product of the elements does not fit
+    /// into a U2048.
+    pub fn write_elems(updates: &[(Key, Account, Account)], block: u64) -> Option<Vec<U2048>> {
+        let mut product = U2048::from(1);
+        let mut elems = Vec::new();
+        for &(key, _, value) in updates.iter().filter(|(_, _, value)| *value != Account::default()) {
+            let elem = Self::write_elem(key, value, block)?;
+            product = product.checked_mul(elem)?;
+            elems.push(elem);
         }
+        return Some(elems);
+    }
 
-        fn on_finalize() {
-            // Remove previous key-value commitment.
-            let (state, product, proof) = accumulator::batch_delete(State::get(), &WitnessData::get());
-            Self::deposit_event(Event::Deletion(state, product, proof));
+    /// Verifies the opening of the value committed at a key against the current state. The membership
+    /// witness proves that the element written by the named block is still contained in the
+    /// accumulator. Since each key holds a single element, whose marker is unique to the block, the
+    /// block is the last one that wrote the key. The non-membership witness is referenced from the
+    /// checkpoint of that block and proves that none of the zero bits of the value were written by it.
+    /// As a result, the witnesses only depend on the elements added by a single block instead of all of
+    /// the elements in the accumulator.
+    /// NOTE: Once the checkpoint has been pruned, the non-membership witness is referenced from the
+    /// generator and must be created from the product of all of the elements in the accumulator. Any
+    /// debit or credit writes the key again. An empty key does not contain any element, so its opening
+    /// proves that none of its bit indices are contained in the accumulator, which is also referenced
+    /// from the generator.
+    pub fn verify_opening(key: Key, value: Account, opening: Opening) -> bool {
+        if value == Account::default() {
+            return vc::verify_at_key(Self::generator(), State::get(), key, value, opening.pi_i, opening.pi_e);
+        }
+        let (deleted_state, added_state) = Self::checkpoint(opening.block)
+            .unwrap_or((Self::generator(), State::get()));
+        let (binary_vec, indices) = vc::convert_key_value(&[key], &[value]);
+        let (_, p_zeros) = binary::get_bit_elems(&binary_vec, &indices);
+        match (opening.pi_i, opening.pi_e, Self::write_elem(key, value, opening.block)) {
+            (Witness::MemWit(pi_i), Witness::NonMemWit(pi_e), Some(elem)) => {
+                return witnesses::verify_mem_wit(State::get(), pi_i, elem)
+                    && witnesses::verify_non_mem_wit(deleted_state, added_state, pi_e, p_zeros);
+            },
+            _ => return false,
+        }
+    }
 
-            // Get the integer representations of the new key-value pairs.
-            let elems: Vec<U2048> = NewKeyValuePairs::get()
-                .into_iter()
-                .enumerate()
-                .map(|(_, (key, value))| -> U2048 {
-                    let (binary_vec, indices) = vc::convert_key_value(&[key as usize], &[value]);
-                    let (p_ones, _) = binary::get_bit_elems(&binary_vec, &indices);
-                    return p_ones;
-                })
-                .collect();
+    /// Opens a value that has been written to a key by the given block, where "additions" is the product
+    /// of the elements added by that block(see the "Addition" event). The membership witness is valid
+    /// for the state after the block and must be updated for every later block that modifies the state
+    /// (see "witnesses::update_mem_wit"). Returns None if the block did not write the value.
+    pub fn open(key: Key, value: Account, block: u64, additions: U2048) -> Option<Opening> {
+        let (deleted_state, _) = Self::checkpoint(block)?;
+        let (binary_vec, indices) = vc::convert_key_value(&[key], &[value]);
+        let (_, p_zeros) = binary::get_bit_elems(&binary_vec, &indices);
+        if subroutines::bezout(additions, p_zeros).is_none() {
+            return None;
+        }
+        let pi_i = witnesses::mem_wit_create(deleted_state, additions, Self::write_elem(key, value, block)?)?;
+        let pi_e = witnesses::non_mem_wit_create(deleted_state, additions, p_zeros);
+        return Some(Opening { block, pi_i: Witness::MemWit(pi_i), pi_e: Witness::NonMemWit(pi_e) });
+    }
 
-            // Add updated key-value pairs.
-            let (state, product, proof) = accumulator::batch_add(state, &elems);
-            Self::deposit_event(Event::Addition(state, product, proof));
+    /// Opens a value whose checkpoint has been pruned, where "witness" is the updated membership witness
+    /// of the written element and "product" is the product of all of the elements in the accumulator.
+    pub fn open_pruned(key: Key, value: Account, block: u64, witness: U2048, product: U2048) -> Option<Opening> {
+        let (binary_vec, indices) = vc::convert_key_value(&[key], &[value]);
+        let (_, p_zeros) = binary::get_bit_elems(&binary_vec, &indices);
+        if subroutines::bezout(product, p_zeros).is_none() {
+            return None;
+        }
+        let pi_e = witnesses::non_mem_wit_create(Self::generator(), product, p_zeros);
+        return Some(Opening { block, pi_i: Witness::MemWit(witness), pi_e: Witness::NonMemWit(pi_e) });
+    }
 
-            // Update accumulator
-            State::put(state);
+    /// Opens an empty key, where "product" is the product of all of the elements in the accumulator.
+    pub fn open_empty(key: Key, product: U2048) -> Option<Opening> {
+        let (pi_i, pi_e) = vc::open_at_key(Self::generator(), product, key, Account::default())?;
+        return Some(Opening { block: 0, pi_i, pi_e });
+    }
 
-            // Clear storage items
-            WitnessData::kill();
-            NewKeyValuePairs::kill();
+    /// Returns the value committed at a key and the value it has been updated to in this block. The
+    /// claimed value and its opening are only verified the first time that a key is updated in a
+    /// block, since later updates are applied on top of the updated value. Does not modify storage.
+    pub fn load(key: Key, value: Account, opening: Opening) -> rstd::result::Result<(Account, Account), &'static str> {
+        if let Some((_, committed, pending)) = Self::get_updates().into_iter().find(|(k, _, _)| *k == key) {
+            return Ok((committed, pending));
+        }
+        ensure!(Self::verify_opening(key, value, opening), "Opening is invalid.");
+        return Ok((value, value));
+    }
+
+    /// Queues the replacement of the values committed at a set of keys until the block is finalized.
+    /// Each write consists of the key, its committed value, its new value and the opening of the
+    /// committed value. Used by transfers and other modules that modify balances, so the keys must have
+    /// been loaded beforehand. The committed element is only deleted the first time that a key is
+    /// updated in a block.
+    /// NOTE: The products of the elements that the block deletes and adds must fit into a U2048, so the
+    /// writes are rejected without modifying storage if either of them would overflow or if a key is
+    /// outside of the key space.
+    pub fn store(writes: &[(Key, Account, Account, Opening)]) -> Result {
+        let mut updates = Self::get_updates();
+        let mut witness_data = Self::get_witness_data();
+        for &(key, committed, new_value, opening) in writes {
+            ensure!(key < T::KeySpace::get(), "Key is outside of the key space.");
+            if let Some(update) = updates.iter_mut().find(|(k, _, _)| *k == key) {
+                update.2 = new_value;
+                continue;
+            }
+            // An empty key does not contain any element that must be deleted.
+            if let (Witness::MemWit(witness), true) = (opening.pi_i, committed != Account::default()) {
+                let elem = Self::write_elem(key, committed, opening.block).ok_or("Committed value is too large.")?;
+                witness_data.push((elem, witness));
+            }
+            updates.push((key, committed, new_value));
         }
+
+        let block = <system::Module<T>>::block_number().saturated_into::<u64>();
+        ensure!(witness_data.iter().try_fold(U2048::from(1), |product, (elem, _)| product.checked_mul(*elem)).is_some(),
+                "Too many values are deleted in this block.");
+        ensure!(Self::write_elems(&updates, block).is_some(), "Too many values are written in this block.");
+        WitnessData::put(witness_data);
+        Updates::put(updates);
+        Ok(())
+    }
+
+    /// Returns whether the value committed at a key is already updated in this block.
+    pub fn is_pending(key: Key) -> bool {
+        return Self::get_updates().iter().any(|(k, _, _)| *k == key);
     }
 }
 
@@ -136,8 +380,10 @@ decl_module! {
 mod tests {
     use super::*;
 
+    use std::{cell::Cell, collections::HashMap};
+    use proptest::{prelude::*, test_runner::TestRunner};
     use runtime_io::with_externalities;
-    use primitives::{H256, Blake2Hasher};
+    use primitives::{Blake2Hasher, Pair};
     use support::{impl_outer_origin, assert_ok, parameter_types};
     use sr_primitives::{
         traits::{BlakeTwo256, IdentityLookup, OnFinalize}, testing::Header, weights::Weight, Perbill,
@@ -157,7 +403,8 @@ mod tests {
         pub const MaximumBlockWeight: Weight = 1024;
         pub const MaximumBlockLength: u32 = 2 * 1024;
         pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
-        pub const KeySpace: u8 = 255;
+        pub const KeySpace: Key = Key::max_value();
+        pub const CheckpointWindow: u64 = 8;
     }
 
     impl system::Trait for Test {
@@ -182,6 +429,7 @@ mod tests {
     impl Trait for Test {
         type Event = ();
         type KeySpace = KeySpace;
+        type CheckpointWindow = CheckpointWindow;
     }
 
     type StatelessAccounts = Module<Test>;
@@ -193,84 +441,581 @@ mod tests {
         system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
     }
 
+    /// Returns a key pair along with its public key and the key derived from it.
+    fn account(seed: u8) -> (sr25519::Pair, H256, Key) {
+        let pair = sr25519::Pair::from_seed(&[seed; 32]);
+        let pub_key = H256::from_slice(pair.public().as_ref());
+        return (pair, pub_key, StatelessAccounts::account_key(&pub_key).unwrap());
+    }
+
+    /// A value tracked by the wallet along with the block that wrote it, the product of the elements
+    /// added by that block, the written element and its membership witness.
+    #[derive(Clone, Copy)]
+    struct Tracked {
+        value: Account,
+        block: u64,
+        additions: U2048,
+        elem: U2048,
+        witness: U2048,
+    }
+
+    /// Tracks the values of keys like a client would. Values are opened against the checkpoints of the
+    /// blocks that wrote them and their membership witnesses are updated by every later block, so an
+    /// opening only needs the product of all of the elements in the accumulator once its checkpoint has
+    /// been pruned.
+    #[derive(Default)]
+    struct Wallet {
+        keys: HashMap<Key, Tracked>,
+    }
+
+    impl Wallet {
+        /// Returns the value of a key along with its opening against the current state.
+        fn open(&self, key: Key) -> (Account, Opening) {
+            match self.keys.get(&key) {
+                Some(tracked) => {
+                    let opening = match StatelessAccounts::checkpoint(tracked.block) {
+                        Some(_) => StatelessAccounts::open(key, tracked.value, tracked.block, tracked.additions).unwrap(),
+                        None => StatelessAccounts::open_pruned(key, tracked.value, tracked.block, tracked.witness, self.product()).unwrap(),
+                    };
+                    return (tracked.value, Opening { pi_i: Witness::MemWit(tracked.witness), ..opening });
+                },
+                None => return (Account::default(), StatelessAccounts::open_empty(key, self.product()).unwrap()),
+            }
+        }
+
+        /// Returns the product of all of the elements in the accumulator, which is only needed to prove
+        /// that a key is empty or to open a value whose checkpoint has been pruned.
+        fn product(&self) -> U2048 {
+            return self.keys.values().fold(U2048::from(1), |product, tracked| product * tracked.elem);
+        }
+
+        /// Mints a balance at an empty key, which is written when the block is finalized.
+        fn mint(&self, key: Key, balance: Balance) -> Result {
+            let proof = vc::prove_empty_key::<Account>(StatelessAccounts::generator(), self.product(), key);
+            return StatelessAccounts::mint(Origin::signed(1), key, balance, proof);
+        }
+
+        /// Creates a transfer signed by "pair" that opens the tracked values of both keys.
+        fn transfer(&self, pair: &sr25519::Pair, sender_key: Key, receiver_key: Key, amount: Balance) -> Transaction {
+            let (sender, sender_opening) = self.open(sender_key);
+            let (receiver, receiver_opening) = self.open(receiver_key);
+            let mut transaction = Transaction {
+                sender_key,
+                sender_pub_key: H256::from_slice(pair.public().as_ref()),
+                sender_balance: sender.balance,
+                sender_nonce: sender.nonce,
+                sender_opening,
+                receiver_key,
+                receiver_balance: receiver.balance,
+                receiver_nonce: receiver.nonce,
+                receiver_opening,
+                amount,
+                signature: H512::zero(),
+            };
+            transaction.signature = H512::from(pair.sign(&transaction.signing_payload()).0);
+            return transaction;
+        }
+
+        /// Finalizes the current block and moves on to the next one. The values written by the block are
+        /// tracked from now on and the witnesses of all other values are updated.
+        fn finalize(&mut self) {
+            let block = System::block_number();
+            let deletions = StatelessAccounts::get_witness_data().iter()
+                .fold(U2048::from(1), |product, (elem, _)| product * *elem);
+            let written: Vec<(Key, Account, U2048)> = StatelessAccounts::get_updates()
+                .into_iter()
+                .filter(|(_, _, value)| *value != Account::default())
+                .map(|(key, _, value)| (key, value, StatelessAccounts::write_elem(key, value, block).unwrap()))
+                .collect();
+            let additions = written.iter().fold(U2048::from(1), |product, (_, _, elem)| product * *elem);
+            StatelessAccounts::on_finalize(block);
+
+            let state = StatelessAccounts::get_state();
+            for (key, tracked) in self.keys.iter_mut() {
+                if !written.iter().any(|(k, _, _)| k == key) {
+                    tracked.witness = witnesses::update_mem_wit(tracked.elem, tracked.witness, state, additions, deletions).unwrap();
+                }
+            }
+            if let Some((deleted_state, _)) = StatelessAccounts::checkpoint(block) {
+                for (key, value, elem) in written {
+                    let witness = witnesses::mem_wit_create(deleted_state, additions, elem).unwrap();
+                    self.keys.insert(key, Tracked { value, block, additions, elem, witness });
+                }
+            }
+            System::set_block_number(block + 1);
+        }
+    }
+
     #[test]
     fn test_mint() {
         with_externalities(&mut new_test_ext(), || {
-            let key: u8 = 1;
-            let value: u8 = 10;
-            StatelessAccounts::mint(Origin::signed(1), key, value);
+            let generator = StatelessAccounts::generator();
+            let key: Key = 1;
+            let value: Balance = 10;
+
+            // The key must be empty and inside of the key space.
+            let proof = vc::prove_empty_key::<Account>(generator, U2048::from(1), key);
+            assert_eq!(StatelessAccounts::mint(Origin::signed(1), key, value, proof).is_ok(), true);
+            let max_key = Key::max_value();
+            let max_proof = vc::prove_empty_key::<Account>(generator, U2048::from(1), max_key);
+            assert_eq!(StatelessAccounts::mint(Origin::signed(1), max_key, value, max_proof).is_err(), true);
+
+            // The key is added when the block is finalized, so it cannot be minted twice within the block.
+            assert_eq!(StatelessAccounts::mint(Origin::signed(1), key, value, proof).is_err(), true);
+            assert_eq!(StatelessAccounts::get_state(), generator);
+            let block = System::block_number();
+            StatelessAccounts::on_finalize(block);
+
+            let elem = StatelessAccounts::write_elem(key, Account { balance: value, nonce: 0 }, block).unwrap();
+            let state = subroutines::mod_exp(generator, elem, U2048::from_dec_str(MODULUS).unwrap());
+            assert_eq!(StatelessAccounts::get_state(), state);
+
+            // Minting the same key twice is rejected.
+            assert_eq!(StatelessAccounts::mint(Origin::signed(1), key, value, proof).is_err(), true);
+            assert_eq!(StatelessAccounts::get_state(), state);
+        });
+    }
+
+    #[test]
+    fn test_mint_pending_key() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut wallet = Wallet::default();
+            let (alice_pair, _, alice_key) = account(1);
+            let (_, _, bob_key) = account(2);
+            assert_ok!(wallet.mint(alice_key, 10));
+            wallet.finalize();
+
+            // Credit the empty key of bob.
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), wallet.transfer(&alice_pair, alice_key, bob_key, 3)));
+
+            // The key of bob is still empty in the current state, but it is updated in this block.
+            assert_eq!(wallet.mint(bob_key, 5).is_err(), true);
+        });
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        with_externalities(&mut new_test_ext(), || {
+            let generator = StatelessAccounts::generator();
+            let mut wallet = Wallet::default();
+            let (alice_pair, _, alice_key) = account(1);
+            let (_, _, bob_key) = account(2);
+
+            // Block 1 mints the balance of alice.
+            System::set_block_number(1);
+            assert_ok!(wallet.mint(alice_key, 10));
+            wallet.finalize();
+            let state = StatelessAccounts::get_state();
+            assert_eq!(StatelessAccounts::checkpoint(1), Some((generator, state)));
+
+            // Block 2 does not write any value, so it has no checkpoint.
+            wallet.finalize();
+            assert_eq!(StatelessAccounts::checkpoint(2), None);
+
+            // Block 3 transfers tokens to bob. The state does not change until the block is finalized.
+            let transaction = wallet.transfer(&alice_pair, alice_key, bob_key, 3);
+            assert_eq!(transaction.sender_opening.block, 1);
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), transaction));
+            assert_eq!(StatelessAccounts::get_state(), state);
+            let (state_after_del, _, _) = batch_delete(state, &StatelessAccounts::get_witness_data());
+            wallet.finalize();
+            assert_eq!(StatelessAccounts::checkpoint(3), Some((state_after_del, StatelessAccounts::get_state())));
+
+            // Both values are now opened against the checkpoint of block 3.
+            assert_eq!(wallet.open(alice_key).0, Account { balance: 7, nonce: 1 });
+            assert_eq!(wallet.open(bob_key).0, Account { balance: 3, nonce: 0 });
+            for key in [alice_key, bob_key].iter() {
+                let (value, opening) = wallet.open(*key);
+                assert_eq!(opening.block, 3);
+                assert_eq!(StatelessAccounts::verify_opening(*key, value, opening), true);
+            }
+        });
+    }
+
+    #[test]
+    fn test_pruned_checkpoint() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut wallet = Wallet::default();
+            let (alice_pair, _, alice_key) = account(1);
+            let (_, _, bob_key) = account(2);
+            let key: Key = 1;
+            let window = CheckpointWindow::get();
+
+            // Block 1 mints both balances. Its checkpoint is kept for the blocks of the window.
+            System::set_block_number(1);
+            assert_ok!(wallet.mint(alice_key, 10));
+            assert_ok!(wallet.mint(key, 10));
+            wallet.finalize();
+            for _ in 1..window {
+                wallet.finalize();
+            }
+            assert_eq!(StatelessAccounts::checkpoint(1).is_some(), true);
+            wallet.finalize();
+            assert_eq!(StatelessAccounts::checkpoint(1), None);
+
+            // The values are now opened against the generator and a balance still cannot be increased.
+            let (value, opening) = wallet.open(key);
+            assert_eq!(opening.block, 1);
+            assert_eq!(StatelessAccounts::verify_opening(key, value, opening), true);
+            assert_eq!(StatelessAccounts::verify_opening(key, Account { balance: 11, nonce: 0 }, opening), false);
+
+            // A transfer writes the value again, so it is opened against the new checkpoint.
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), wallet.transfer(&alice_pair, alice_key, bob_key, 3)));
+            let block = System::block_number();
+            wallet.finalize();
+            let (value, opening) = wallet.open(alice_key);
+            assert_eq!(value, Account { balance: 7, nonce: 1 });
+            assert_eq!(opening.block, block);
+            assert_eq!(StatelessAccounts::verify_opening(alice_key, value, opening), true);
+        });
+    }
+
+    #[test]
+    fn test_empty_block() {
+        with_externalities(&mut new_test_ext(), || {
+            let state = StatelessAccounts::get_state();
+            StatelessAccounts::on_finalize(System::block_number());
+            assert_eq!(StatelessAccounts::get_state(), state);
+        });
+    }
+
+    #[test]
+    fn test_forged_opening() {
+        with_externalities(&mut new_test_ext(), || {
+            let generator = StatelessAccounts::generator();
+            let mut wallet = Wallet::default();
+            let key: Key = 1;
+            let value = Account { balance: 10, nonce: 0 };
+            System::set_block_number(1);
+            assert_ok!(wallet.mint(key, value.balance));
+            wallet.finalize();
+            let state = StatelessAccounts::get_state();
+
+            // A higher balance cannot be opened with the opening of the actual balance.
+            let (_, opening) = wallet.open(key);
+            assert_eq!(StatelessAccounts::verify_opening(key, value, opening), true);
+            assert_eq!(StatelessAccounts::verify_opening(key, Account { balance: 11, ..value }, opening), false);
+
+            // The halves of an opening cannot be swapped and the opening must name the block that wrote the value.
+            assert_eq!(StatelessAccounts::verify_opening(key, value, Opening { pi_i: opening.pi_e, pi_e: opening.pi_i, ..opening }), false);
+            assert_eq!(StatelessAccounts::verify_opening(key, value, Opening { block: 2, ..opening }), false);
+
+            // A one bit cannot be opened as a zero with a non-membership witness that is referenced
+            // from the current state instead of the checkpoint. 8 omits the second bit of 10.
+            let forged_value = Account { balance: 8, ..value };
+            let (binary_vec, indices) = vc::convert_key_value(&[key], &[forged_value]);
+            let (_, p_zeros) = binary::get_bit_elems(&binary_vec, &indices);
+            let elem = StatelessAccounts::write_elem(key, value, 1).unwrap();
+            let forged_elem = StatelessAccounts::write_elem(key, forged_value, 1).unwrap();
+            let pi_e = witnesses::non_mem_wit_create(state, U2048::from(1), p_zeros);
+            assert_eq!(witnesses::verify_non_mem_wit(state, state, pi_e, p_zeros), true);
+            let forged = Opening {
+                block: 1,
+                pi_i: Witness::MemWit(witnesses::mem_wit_create(generator, elem, forged_elem).unwrap()),
+                pi_e: Witness::NonMemWit(pi_e),
+            };
+            assert_eq!(StatelessAccounts::verify_opening(key, forged_value, forged), false);
+        });
+    }
+
+    #[test]
+    fn test_signature() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut wallet = Wallet::default();
+            let (alice_pair, alice_pub_key, alice_key) = account(1);
+            let (bob_pair, _, bob_key) = account(2);
+            assert_ok!(wallet.mint(alice_key, 10));
+            assert_ok!(wallet.mint(bob_key, 5));
+            wallet.finalize();
+            let transaction = wallet.transfer(&alice_pair, alice_key, bob_key, 3);
+
+            // The transfer must be signed by the public key that the sender key is derived from.
+            let mut forged = wallet.transfer(&bob_pair, alice_key, bob_key, 3);
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), forged.clone()).is_err(), true);
+            forged.sender_pub_key = alice_pub_key;
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), forged).is_err(), true);
+
+            // The signature covers the nonce and the amount.
+            let mut forged = transaction.clone();
+            forged.amount = 4;
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), forged).is_err(), true);
+            let mut forged = transaction.clone();
+            forged.sender_nonce = 1;
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), forged).is_err(), true);
+
+            // The transfer cannot be replayed within the same block.
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), transaction.clone()));
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), transaction).is_err(), true);
+            assert_eq!(StatelessAccounts::get_witness_data().len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_fan_out() {
+        with_externalities(&mut new_test_ext(), || {
+            let generator = StatelessAccounts::generator();
+            let mut wallet = Wallet::default();
+            let (alice_pair, _, alice_key) = account(1);
+            let (_, _, bob_key) = account(2);
+            let (_, _, carol_key) = account(3);
+            assert_ok!(wallet.mint(alice_key, 10));
+            wallet.finalize();
+
+            // Alice pays bob and carol in the same block, where the second debit uses the next nonce.
+            let to_bob = wallet.transfer(&alice_pair, alice_key, bob_key, 2);
+            let mut to_carol = wallet.transfer(&alice_pair, alice_key, carol_key, 3);
+            to_carol.sender_nonce = 1;
+            to_carol.signature = H512::from(alice_pair.sign(&to_carol.signing_payload()).0);
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), to_bob.clone()));
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), to_bob).is_err(), true);
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), to_carol));
+
+            // The element of alice is deleted once and a single element is added per key.
+            assert_eq!(StatelessAccounts::get_witness_data().len(), 1);
+            assert_eq!(StatelessAccounts::get_updates().len(), 3);
+            let block = System::block_number();
+            wallet.finalize();
+
+            let product = StatelessAccounts::write_elem(alice_key, Account { balance: 5, nonce: 2 }, block).unwrap() *
+                StatelessAccounts::write_elem(bob_key, Account { balance: 2, nonce: 0 }, block).unwrap() *
+                StatelessAccounts::write_elem(carol_key, Account { balance: 3, nonce: 0 }, block).unwrap();
+            assert_eq!(StatelessAccounts::get_state(), subroutines::mod_exp(generator, product, U2048::from_dec_str(MODULUS).unwrap()));
+        });
+    }
 
-            let (binary_vec, indices) = vc::convert_key_value(&[key as usize], &[value]);
-            let (p_ones, _) = binary::get_bit_elems(&binary_vec, &indices);
-            assert_eq!(StatelessAccounts::get_state(), subroutines::mod_exp(U2048::from(2), p_ones, U2048::from_dec_str(MODULUS).unwrap()));
+    #[test]
+    fn test_fan_in() {
+        with_externalities(&mut new_test_ext(), || {
+            let generator = StatelessAccounts::generator();
+            let mut wallet = Wallet::default();
+            let (alice_pair, _, alice_key) = account(1);
+            let (bob_pair, _, bob_key) = account(2);
+            let (_, _, carol_key) = account(3);
+            assert_ok!(wallet.mint(alice_key, 10));
+            assert_ok!(wallet.mint(bob_key, 5));
+            assert_ok!(wallet.mint(carol_key, 1));
+            wallet.finalize();
+
+            // Alice and bob both pay carol in the same block. Both transfers open the committed value of
+            // carol, and the second one is applied on top of the first.
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), wallet.transfer(&alice_pair, alice_key, carol_key, 2)));
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), wallet.transfer(&bob_pair, bob_key, carol_key, 3)));
+
+            // Each committed element is deleted once and a single element is added per key.
+            assert_eq!(StatelessAccounts::get_witness_data().len(), 3);
+            assert_eq!(StatelessAccounts::get_updates().len(), 3);
+            let block = System::block_number();
+            wallet.finalize();
+
+            let new_product = StatelessAccounts::write_elem(alice_key, Account { balance: 8, nonce: 1 }, block).unwrap() *
+                StatelessAccounts::write_elem(bob_key, Account { balance: 2, nonce: 1 }, block).unwrap() *
+                StatelessAccounts::write_elem(carol_key, Account { balance: 6, nonce: 0 }, block).unwrap();
+            assert_eq!(StatelessAccounts::get_state(), subroutines::mod_exp(generator, new_product, U2048::from_dec_str(MODULUS).unwrap()));
         });
     }
 
+    #[test]
+    fn test_many_accounts() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut wallet = Wallet::default();
+            let (bank_pair, _, bank_key) = account(100);
+            let users: Vec<(sr25519::Pair, H256, Key)> = (0..4u8).map(account).collect();
+
+            // Block 1 funds the bank and all users. The proofs that their keys are empty are referenced
+            // from the empty accumulator.
+            System::set_block_number(1);
+            assert_ok!(wallet.mint(bank_key, 0x7fff_ffff));
+            for (_, _, key) in users.iter() {
+                assert_ok!(wallet.mint(*key, 1));
+            }
+            wallet.finalize();
+
+            // The bank pays each user in its own block, which gives every user 15 one bits. The elements
+            // added by a single block must fit into a U2048(see "test_block_capacity").
+            for (_, _, key) in users.iter() {
+                assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), wallet.transfer(&bank_pair, bank_key, *key, 0x7ffe)));
+                wallet.finalize();
+            }
+
+            // The product of all of the elements in the accumulator no longer fits into a U2048, so none
+            // of the values could be opened against the generator.
+            let product = wallet.keys.values().try_fold(U2048::from(1), |product, tracked| product.checked_mul(tracked.elem));
+            assert_eq!(product, None);
+
+            // The first user is still opened against the checkpoint of the block that paid them and
+            // pays the last user.
+            let (first_pair, _, first_key) = &users[0];
+            let last_key = users[users.len() - 1].2;
+            assert_eq!(wallet.open(*first_key).1.block, 2);
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), wallet.transfer(first_pair, *first_key, last_key, 5)));
+            wallet.finalize();
+
+            // Every value opens against the current state and the supply is unchanged.
+            let mut supply: u64 = 0;
+            for key in users.iter().map(|(_, _, key)| *key).chain(Some(bank_key)) {
+                let (value, opening) = wallet.open(key);
+                assert_eq!(StatelessAccounts::verify_opening(key, value, opening), true);
+                supply += value.balance as u64;
+            }
+            assert_eq!(supply, 0x7fff_ffff + users.len() as u64);
+            assert_eq!(wallet.open(*first_key).0, Account { balance: 0x7fff - 5, nonce: 1 });
+            assert_eq!(wallet.open(last_key).0, Account { balance: 0x7fff + 5, nonce: 0 });
+        });
+    }
+
+    #[test]
+    fn test_key_space() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut wallet = Wallet::default();
+            let (alice_pair, _, alice_key) = account(1);
+            assert_ok!(wallet.mint(alice_key, 10));
+            wallet.finalize();
+
+            // Tokens cannot be transferred to a key outside of the key space.
+            let transaction = wallet.transfer(&alice_pair, alice_key, Key::max_value(), 3);
+            assert_eq!(StatelessAccounts::add_transaction(Origin::signed(1), transaction),
+                       Err("Key is outside of the key space."));
+            assert_eq!(StatelessAccounts::get_updates().len(), 0);
+            assert_eq!(StatelessAccounts::get_witness_data().len(), 0);
+        });
+    }
+
+    #[test]
+    fn test_block_capacity() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut wallet = Wallet::default();
+            let keys: Vec<Key> = vec![1, 2, 3];
+            System::set_block_number(1);
+
+            // Each of these values adds 32 primes, so only two of them fit into the elements of a block.
+            assert_ok!(wallet.mint(keys[0], 0x7fff_ffff));
+            assert_ok!(wallet.mint(keys[1], 0x7fff_ffff));
+            let updates = StatelessAccounts::get_updates();
+            assert_eq!(wallet.mint(keys[2], 0x7fff_ffff), Err("Too many values are written in this block."));
+            assert_eq!(StatelessAccounts::get_updates(), updates);
+
+            // The block is finalized without overflowing and the rejected value is minted by the next one.
+            wallet.finalize();
+            assert_ok!(wallet.mint(keys[2], 0x7fff_ffff));
+            wallet.finalize();
+            for key in keys {
+                let (value, opening) = wallet.open(key);
+                assert_eq!(value.balance, 0x7fff_ffff);
+                assert_eq!(StatelessAccounts::verify_opening(key, value, opening), true);
+            }
+        });
+    }
+
+    #[test]
+    fn test_supply_conservation() {
+        let accounts: Vec<(sr25519::Pair, H256, Key)> = (1..4u8).map(account).collect();
+        // The balances have at most 12 bits, so that the elements of all keys fit into a U2048 and no
+        // receiver balance overflows. Each transfer is given by the sender, the offset of the receiver and
+        // the amount as a percentage of the sender balance, so about one in eleven transfers spends more
+        // than the balance. Every transfer is applied in its own block.
+        let balances = prop::collection::vec(0..=0xfffu32, accounts.len());
+        let transfers = prop::collection::vec((0..accounts.len(), 1..accounts.len(), 0..=110u64), 1..5);
+        let applied = Cell::new(0);
+
+        let mut runner = TestRunner::new(ProptestConfig::with_cases(8));
+        runner.run(&(balances, transfers), |(balances, transfers)| {
+            return with_externalities(&mut new_test_ext(), || {
+                let mut wallet = Wallet::default();
+                System::set_block_number(1);
+                for ((_, _, key), balance) in accounts.iter().zip(balances.iter()) {
+                    prop_assert_eq!(wallet.mint(*key, *balance).is_ok(), true);
+                }
+                wallet.finalize();
+                let supply: u64 = balances.iter().map(|balance| *balance as u64).sum();
+
+                for (sender_index, offset, percentage) in transfers {
+                    let (sender_pair, _, sender_key) = &accounts[sender_index];
+                    let receiver_key = accounts[(sender_index + offset) % accounts.len()].2;
+                    let (sender, receiver) = (wallet.open(*sender_key).0, wallet.open(receiver_key).0);
+                    let amount = (sender.balance as u64 * percentage / 100) as Balance;
+                    let transaction = wallet.transfer(sender_pair, *sender_key, receiver_key, amount);
+                    let result = StatelessAccounts::add_transaction(Origin::signed(1), transaction);
+                    let state = StatelessAccounts::get_state();
+                    wallet.finalize();
+
+                    if amount <= sender.balance {
+                        prop_assert_eq!(result.is_ok(), true);
+                        prop_assert_eq!(wallet.open(*sender_key).0, Account { balance: sender.balance - amount, nonce: sender.nonce + 1 });
+                        prop_assert_eq!(wallet.open(receiver_key).0, Account { balance: receiver.balance + amount, ..receiver });
+                        applied.set(applied.get() + 1);
+                    }
+                    else {
+                        prop_assert_eq!(result.is_err(), true);
+                        prop_assert_eq!(StatelessAccounts::get_state(), state);
+                    }
+
+                    // Every key opens against the new commitment and the balances sum up to the minted supply
+                    let mut total: u64 = 0;
+                    for (_, _, key) in accounts.iter() {
+                        let (value, opening) = wallet.open(*key);
+                        prop_assert_eq!(StatelessAccounts::verify_opening(*key, value, opening), true);
+                        total += value.balance as u64;
+                    }
+                    prop_assert_eq!(total, supply);
+                }
+                return Ok(());
+            });
+        }).unwrap();
+
+        // Not every transfer is rejected
+        assert_eq!(applied.get() > 0, true);
+    }
+
     #[test]
     fn test_transaction() {
         with_externalities(&mut new_test_ext(), || {
-            let generator = StatelessAccounts::get_state();
+            let mut wallet = Wallet::default();
 
-            // Define keys for alice and bob
-            let alice_key: u8 = 12;
-            let bob_key: u8 = 58;
+            // Derive keys for alice and bob from their public keys
+            let (alice_pair, _, alice_key) = account(1);
+            let (_, _, bob_key) = account(2);
+            assert!(alice_key != bob_key);
 
-            // Define balances for alice and bob
-            let alice_balance: u8 = 10;
-            let bob_balance: u8 = 5;
+            // Define accounts for alice and bob
+            let alice = Account { balance: 10, nonce: 0 };
+            let bob = Account { balance: 5, nonce: 0 };
 
             // Mint tokens for each user
-            StatelessAccounts::mint(Origin::signed(1), alice_key, alice_balance);
-            StatelessAccounts::mint(Origin::signed(1), bob_key, bob_balance);
-
-            // Derive integer representations for manual testing
-            let alice_elem = vc::get_key_value_elem(alice_key as usize, alice_balance);  // This value would be received from the emitted event.
-            let bob_elem = vc::get_key_value_elem(bob_key as usize, bob_balance);   // This value would be received from the emitted event.
-            let product = alice_elem * bob_elem;
+            assert_ok!(wallet.mint(alice_key, alice.balance));
+            assert_ok!(wallet.mint(bob_key, bob.balance));
+            wallet.finalize();
 
             // Get state after minting
             let state_after_mint = StatelessAccounts::get_state();
 
-            // Get openings for each user
-            let (alice_pi_i, alice_pi_e) = vc::open_at_key(generator, product, alice_key as usize, alice_balance);
-            let (bob_pi_i, bob_pi_e) = vc::open_at_key(generator, product, bob_key as usize, bob_balance);
-
-            // Construct transaction
-            let transaction = Transaction {
-                sender_key: alice_key,
-                sender_balance: alice_balance,
-                sender_elem: alice_elem,
-                sender_opening: (alice_pi_i, alice_pi_e),
-                receiver_key: bob_key,
-                receiver_balance: bob_balance,
-                receiver_elem: bob_elem,
-                receiver_opening: (bob_pi_i, bob_pi_e),
-                amount: 3,
-            };
-
-            // Submit transaction
-            StatelessAccounts::add_transaction(Origin::signed(1), transaction, generator);
+            // Construct and submit transaction
+            let transaction = wallet.transfer(&alice_pair, alice_key, bob_key, 3);
+            assert_ok!(StatelessAccounts::add_transaction(Origin::signed(1), transaction));
 
             // Manually get the state after deletion for manual testing
             let (state_after_del, _, _) = batch_delete(state_after_mint, &StatelessAccounts::get_witness_data());
 
             // Finalize block
-            StatelessAccounts::on_finalize(System::block_number());
+            let block = System::block_number();
+            StatelessAccounts::on_finalize(block);
 
             // Get the new state
             let new_state = StatelessAccounts::get_state();
+            assert_eq!(StatelessAccounts::checkpoint(block), Some((state_after_del, new_state)));
 
-            // Derive integer representations for alice and bob's new key-value stores
-            let new_alice_elem = vc::get_key_value_elem(alice_key as usize, alice_balance-3);  // This value would be received from the emitted event.
-            let new_bob_elem = vc::get_key_value_elem(bob_key as usize, bob_balance+3);  // This value would be received from the emitted event.
+            // Derive integer representations for alice and bob's new key-value stores. The nonce of alice has been incremented.
+            let new_alice = Account { balance: alice.balance-3, nonce: 1 };
+            let new_bob = Account { balance: bob.balance+3, nonce: 0 };
+            let new_alice_elem = StatelessAccounts::write_elem(alice_key, new_alice, block).unwrap();  // This value would be received from the emitted event.
+            let new_bob_elem = StatelessAccounts::write_elem(bob_key, new_bob, block).unwrap();  // This value would be received from the emitted event.
 
             // Create openings with the new balances
-            let (alice_pi_i_new, alice_pi_e_new) = vc::open_at_key(state_after_del, new_alice_elem*new_bob_elem, alice_key as usize, alice_balance-3);
-            let (bob_pi_i_new, bob_pi_e_new) = vc::open_at_key(state_after_del, new_alice_elem*new_bob_elem, bob_key as usize, bob_balance+3);
+            let alice_opening = StatelessAccounts::open(alice_key, new_alice, block, new_alice_elem*new_bob_elem).unwrap();
+            let bob_opening = StatelessAccounts::open(bob_key, new_bob, block, new_alice_elem*new_bob_elem).unwrap();
 
             // Verify that the openings are valid
-            assert_eq!(vc::verify_at_key(state_after_del, new_state, alice_key as usize, alice_balance-3, alice_pi_i_new, alice_pi_e_new), true);
-            assert_eq!(vc::verify_at_key(state_after_del, new_state, bob_key as usize, bob_balance+3, bob_pi_i_new, bob_pi_e_new), true);
+            assert_eq!(StatelessAccounts::verify_opening(alice_key, new_alice, alice_opening), true);
+            assert_eq!(StatelessAccounts::verify_opening(bob_key, new_bob, bob_opening), true);
         });
     }
 }
@@ -4,44 +4,129 @@
 use accumulator::*;
 use rstd::prelude::Vec;
 use bit_vec::BitVec;
-use crate::binary;
+use crate::binary::{self, Index};
 
-type ValueType = u8;
+/// Identifies the value at a position of the vector. The bit indices of a key are computed as
+/// "Index" values, which are wide enough that key * BITS cannot overflow. Therefore, the indices of
+/// different keys never collide.
+pub type Key = u64;
 
-/// Commit to a set of keys and corresponding values.
-pub fn commit(accumulator: U2048, keys: &[usize], values: &[ValueType]) -> (U2048, U2048) {
+/// Unsigned integer types that can be committed as values. Each value occupies "BITS" consecutive
+/// bit indices starting at key * "BITS".
+/// NOTE: The prime representations of the bit indices are below 2^32 and the product of the primes of
+/// all ones(or all zeros) of a value must fit into a U2048, so values are at most 64 bits wide. "u128"
+/// is deliberately not implemented: the primes of its 128 bit indices take about 3900 bits, so an empty
+/// key could not be proven(see "prove_empty_key") and most values could neither be committed nor opened.
+pub trait Value: Copy + Default {
+    /// The width of the value in bits.
+    const BITS: usize;
+    /// Returns the little endian byte representation of the value.
+    fn to_bytes(self) -> Vec<u8>;
+}
+
+macro_rules! impl_value {
+    ($($t:ty),*) => {
+        $(
+            impl Value for $t {
+                const BITS: usize = core::mem::size_of::<$t>() * 8;
+                fn to_bytes(self) -> Vec<u8> {
+                    return self.to_le_bytes().to_vec();
+                }
+            }
+        )*
+    }
+}
+
+impl_value!(u8, u16, u32, u64);
+
+/// Commit to a set of keys and corresponding values. Returns None if the number of keys and values
+/// differs or if a key occurs more than once.
+pub fn commit<V: Value>(accumulator: U2048, keys: &[Key], values: &[V]) -> Option<(U2048, U2048)> {
+    if keys.len() != values.len() {
+        return None;
+    }
     let (binary_vec, indices) = convert_key_value(keys, values);
     return binary::commit(accumulator, &binary_vec, &indices);
 }
 
 /// Open a commitment for a value at a specific key. This function would be immediately called by a
-/// user following a relevant state commitment.
-pub fn open_at_key(old_state: U2048, product: U2048, key: usize, value: ValueType) -> (Witness, Witness) {
-    let (binary_vec, indices) = convert_key_value(&[key], &[value]);
-    return binary::batch_open(old_state, product, &binary_vec, &indices);
+/// user following a relevant state commitment. Returns None if the value is not committed at the key.
+pub fn open_at_key<V: Value>(old_state: U2048, product: U2048, key: Key, value: V) -> Option<(Witness, Witness)> {
+    return open_keys(old_state, product, &[key], &[value]);
 }
 
 /// Verify a commitment for a value at a specific key.
-pub fn verify_at_key(old_state: U2048, accumulator: U2048, key: usize, value: ValueType, pi_i: Witness, pi_e: Witness) -> bool {
-    let (binary_vec, indices) = convert_key_value(&[key], &[value]);
+pub fn verify_at_key<V: Value>(old_state: U2048, accumulator: U2048, key: Key, value: V, pi_i: Witness, pi_e: Witness) -> bool {
+    return verify_keys(old_state, accumulator, &[key], &[value], pi_i, pi_e);
+}
+
+/// Open a commitment for the values at a set of keys. The opening consists of a single membership
+/// and a single non-membership witness regardless of the number of keys. Returns None if the input is
+/// invalid(see "commit") or if the values are not committed at the keys.
+/// NOTE: The product of the prime representations of all opened bits must fit into a U2048, which
+/// limits the number of bits that can be opened at once.
+pub fn open_keys<V: Value>(old_state: U2048, product: U2048, keys: &[Key], values: &[V]) -> Option<(Witness, Witness)> {
+    if keys.len() != values.len() {
+        return None;
+    }
+    let (binary_vec, indices) = convert_key_value(keys, values);
+    return binary::batch_open(old_state, product, &binary_vec, &indices);
+}
+
+/// Verify an opening for the values at a set of keys(see "open_keys").
+pub fn verify_keys<V: Value>(old_state: U2048, accumulator: U2048, keys: &[Key], values: &[V], pi_i: Witness, pi_e: Witness) -> bool {
+    if keys.len() != values.len() {
+        return false;
+    }
+    let (binary_vec, indices) = convert_key_value(keys, values);
     return binary::batch_verify(old_state, accumulator, &binary_vec, &indices, pi_i, pi_e);
 }
 
-/// Update the values for a set of keys. Assumes key-value pairs are valid.
-pub fn update(accumulator: U2048, old_state: U2048, agg: U2048, keys: &[usize], values: &[ValueType]) -> U2048 {
+/// Update the values for a set of keys. Returns None if the input is invalid(see "commit").
+pub fn update<V: Value>(accumulator: U2048, old_state: U2048, agg: U2048, keys: &[Key], values: &[V]) -> Option<U2048> {
+    if keys.len() != values.len() {
+        return None;
+    }
     let (binary_vec, indices) = convert_key_value(keys, values);
     return binary::update(accumulator, old_state, agg, &binary_vec, &indices);
 }
 
+/// Proves that none of the bit indices of a key are contained in the accumulator, i.e. that the
+/// key is empty. "product" must be the product of all elements accumulated on top of "generator".
+pub fn prove_empty_key<V: Value>(generator: U2048, product: U2048, key: Key) -> Witness {
+    let elem = get_key_elem::<V>(key);
+    return Witness::NonMemWit(witnesses::non_mem_wit_create(generator, product, elem));
+}
+
+/// Verifies that a key is empty given a non-membership proof relative to the generator.
+pub fn verify_empty_key<V: Value>(generator: U2048, accumulator: U2048, key: Key, proof: Witness) -> bool {
+    match proof {
+        Witness::NonMemWit(witness) => {
+            return witnesses::verify_non_mem_wit(generator, accumulator, witness, get_key_elem::<V>(key));
+        },
+        Witness::MemWit(_) => {
+            return false;
+        },
+    }
+}
+
+/// Returns the product of the prime representations of all bit indices of a key.
+fn get_key_elem<V: Value>(key: Key) -> U2048 {
+    let (binary_vec, indices) = convert_key_value(&[key], &[V::default()]);
+    let (_, p_zeros) = binary::get_bit_elems(&binary_vec, &indices);
+    return p_zeros;
+}
+
 /// Converts key-value pairs into a binary representation of the values along with corresponding
 /// indices.
-pub fn convert_key_value(keys: &[usize], values: &[ValueType]) -> (Vec<bool>, Vec<usize>) {
+pub fn convert_key_value<V: Value>(keys: &[Key], values: &[V]) -> (Vec<bool>, Vec<Index>) {
     let mut binary_vec: Vec<bool> = [].to_vec();
-    let mut indices: Vec<usize> = [].to_vec();
+    let mut indices: Vec<Index> = [].to_vec();
     for (i, &value) in values.iter().enumerate() {
         let mut value_vec = to_binary(value);
-        let offset = core::mem::size_of::<ValueType>()*8;
-        let mut index_vec = (keys[i]*offset..keys[i]*offset+offset).collect();
+        let offset = V::BITS as Index;
+        let start = keys[i] as Index * offset;
+        let mut index_vec = (start..start+offset).collect();
         binary_vec.append(&mut value_vec);
         indices.append(&mut index_vec);
     }
@@ -49,15 +134,15 @@ pub fn convert_key_value(keys: &[usize], values: &[ValueType]) -> (Vec<bool>, Ve
 }
 
 /// Converts an element to a binary representation.
-pub fn to_binary(elem: ValueType) -> Vec<bool> {
-    let byte_vec = elem.to_le_bytes().to_vec();
+pub fn to_binary<V: Value>(elem: V) -> Vec<bool> {
+    let byte_vec = elem.to_bytes();
     let bv = BitVec::from_bytes(&byte_vec);
     return bv.iter().collect::<Vec<bool>>();
 }
 
 /// Quick helper function that gets the product of the accumulated elements for a given
 /// key-value pair.
-pub fn get_key_value_elem(key: usize, value: ValueType) -> U2048 {
+pub fn get_key_value_elem<V: Value>(key: Key, value: V) -> U2048 {
     let (binary_vec, indices) = convert_key_value(&[key], &[value]);
     let (elem, _) = binary::get_bit_elems(&binary_vec, &indices);
     return elem;
@@ -69,7 +154,7 @@ mod tests {
 
     #[test]
     fn test_to_binary() {
-        let elem: ValueType = 6;
+        let elem: u8 = 6;
         let bv = to_binary(elem);
         assert_eq!(bv, vec![false, false, false, false, false, true, true, false]);
     }
@@ -78,15 +163,15 @@ mod tests {
     fn test_commit() {
         let accumulator: U2048 = U2048::from(2);
         let keys = [0, 1];
-        let values = vec![4, 7];
+        let values: Vec<u8> = vec![4, 7];
 
-        let (new_accumulator, _) = commit(accumulator, &keys, &values);
+        let (new_accumulator, _) = commit(accumulator, &keys, &values).unwrap();
 
         // Manual check
-        let check_product = subroutines::hash_to_prime(&(5 as usize).to_le_bytes())
-            * subroutines::hash_to_prime(&(13 as usize).to_le_bytes())
-            * subroutines::hash_to_prime(&(14 as usize).to_le_bytes())
-            * subroutines::hash_to_prime(&(15 as usize).to_le_bytes());
+        let check_product = binary::index_to_prime(5)
+            * binary::index_to_prime(13)
+            * binary::index_to_prime(14)
+            * binary::index_to_prime(15);
 
         assert_eq!(new_accumulator, subroutines::mod_exp(U2048::from(2), U2048::from(check_product), U2048::from_dec_str(MODULUS).unwrap()));
     }
@@ -94,7 +179,7 @@ mod tests {
     #[test]
     fn test_convert() {
         let keys = vec![0, 1];
-        let values = vec![4, 7];
+        let values: Vec<u8> = vec![4, 7];
         let (binary_vec, indices) = convert_key_value(&keys, &values);
         assert_eq!(binary_vec, vec![false, false, false, false, false, true, false, false, false, false, false, false,
             false, true, true, true]);
@@ -105,24 +190,151 @@ mod tests {
     fn test_vc_open_and_verify() {
         let accumulator: U2048 = U2048::from(2);
         let keys = vec![0, 1];
-        let values = vec![4, 7];
-        let (new_accumulator, product) = commit(accumulator, &keys, &values);
+        let values: Vec<u8> = vec![4, 7];
+        let (new_accumulator, product) = commit(accumulator, &keys, &values).unwrap();
+
+        let (pi_i, pi_e) = open_at_key(accumulator, product, 1, 7u8).unwrap();
+
+        assert_eq!(verify_at_key(accumulator, new_accumulator, 1, 7u8, pi_i, pi_e), true);
+        assert_eq!(verify_at_key(accumulator, new_accumulator, 0, 7u8, pi_i, pi_e), false);
+        assert_eq!(verify_at_key(accumulator, new_accumulator, 1, 4u8, pi_i, pi_e), false);
+    }
+
+    #[test]
+    fn test_open_and_verify_keys() {
+        let accumulator: U2048 = U2048::from(2);
+        let keys = vec![0, 1, 2, 3];
+        let values: Vec<u8> = vec![4, 7, 0, 9];
+        let (new_accumulator, product) = commit(accumulator, &keys, &values).unwrap();
+
+        // A single opening covers a subset of the keys.
+        let (pi_i, pi_e) = open_keys(accumulator, product, &[1, 3], &[7u8, 9u8]).unwrap();
+        assert_eq!(verify_keys(accumulator, new_accumulator, &[1, 3], &[7u8, 9u8], pi_i, pi_e), true);
+        assert_eq!(verify_keys(accumulator, new_accumulator, &[1, 3], &[7u8, 8u8], pi_i, pi_e), false);
+        assert_eq!(verify_keys(accumulator, new_accumulator, &[3, 1], &[7u8, 9u8], pi_i, pi_e), false);
+        assert_eq!(verify_keys(accumulator, new_accumulator, &[1], &[7u8], pi_i, pi_e), false);
+
+        // Opening all keys, including an empty one.
+        let (pi_i, pi_e) = open_keys(accumulator, product, &keys, &values).unwrap();
+        assert_eq!(verify_keys(accumulator, new_accumulator, &keys, &values, pi_i, pi_e), true);
+        assert_eq!(verify_keys(accumulator, new_accumulator, &keys, &[4u8, 7u8, 1u8, 9u8], pi_i, pi_e), false);
+    }
 
-        let (pi_i, pi_e) = open_at_key(accumulator, product, 1, 7);
+    #[test]
+    fn test_invalid_keys() {
+        let accumulator: U2048 = U2048::from(2);
+        assert_eq!(commit(accumulator, &[0, 1], &[4u8]), None);
+        assert_eq!(commit(accumulator, &[0], &[4u8, 7u8]), None);
+        assert_eq!(commit(accumulator, &[1, 1], &[4u8, 7u8]), None);
+        let (new_accumulator, product) = commit(accumulator, &[0, 1], &[4u8, 7u8]).unwrap();
+
+        // A key cannot be opened to a value other than the committed one, nor to two values at once.
+        assert_eq!(open_at_key(accumulator, product, 1, 3u8), None);
+        assert_eq!(open_keys(accumulator, product, &[1, 1], &[7u8, 3u8]), None);
+        assert_eq!(open_keys(accumulator, product, &[0, 1], &[4u8]), None);
+        let (pi_i, pi_e) = open_at_key(accumulator, product, 1, 7u8).unwrap();
+        assert_eq!(verify_keys(accumulator, new_accumulator, &[1, 1], &[7u8, 7u8], pi_i, pi_e), false);
+        assert_eq!(verify_keys(accumulator, new_accumulator, &[1], &[7u8, 3u8], pi_i, pi_e), false);
+        assert_eq!(update(new_accumulator, accumulator, product, &[1, 1], &[3u8, 4u8]), None);
+    }
+
+    #[test]
+    fn test_convert_widths() {
+        let (binary_vec, indices) = convert_key_value(&[2], &[0x0102u16]);
+        assert_eq!(indices, (32..48).collect::<Vec<Index>>());
+        assert_eq!(binary_vec, [to_binary(0x02u8), to_binary(0x01u8)].concat());
+
+        let (binary_vec, indices) = convert_key_value(&[1, 3], &[1u32, 2u32]);
+        assert_eq!(indices, [(32..64).collect::<Vec<Index>>(), (96..128).collect::<Vec<Index>>()].concat());
+        assert_eq!(binary_vec.iter().filter(|bit| **bit).count(), 2);
+
+        let (binary_vec, indices) = convert_key_value(&[1], &[u64::max_value()]);
+        assert_eq!(indices, (64..128).collect::<Vec<Index>>());
+        assert_eq!(binary_vec, vec![true; 64]);
+    }
+
+    #[test]
+    fn test_vc_full_width() {
+        // All 64 bits of a value are either ones or zeros.
+        let accumulator: U2048 = U2048::from(2);
+        let keys = vec![0, 1];
+        let values: Vec<u64> = vec![u64::max_value(), 0];
+        let (new_accumulator, product) = commit(accumulator, &keys, &values).unwrap();
+
+        for (&key, &value) in keys.iter().zip(values.iter()) {
+            let (pi_i, pi_e) = open_at_key(accumulator, product, key, value).unwrap();
+            assert_eq!(verify_at_key(accumulator, new_accumulator, key, value, pi_i, pi_e), true);
+            assert_eq!(open_at_key(accumulator, product, key, value ^ 1), None);
+        }
+
+        let proof = prove_empty_key::<u64>(accumulator, product, 2);
+        assert_eq!(verify_empty_key::<u64>(accumulator, new_accumulator, 2, proof), true);
+    }
+
+    #[test]
+    fn test_large_keys() {
+        // The indices of the largest keys do not overflow nor collide.
+        let max = Key::max_value();
+        let (_, indices) = convert_key_value(&[max - 1, max], &[1u64, 1u64]);
+        assert_eq!(indices[0], (max - 1) as Index * 64);
+        assert_eq!(indices[63] + 1, indices[64]);
+        assert_eq!(indices[127], max as Index * 64 + 63);
+
+        let accumulator: U2048 = U2048::from(2);
+        let keys = vec![1 << 40, max];
+        let values: Vec<u16> = vec![300, 7];
+        let (new_accumulator, product) = commit(accumulator, &keys, &values).unwrap();
+        let (pi_i, pi_e) = open_at_key(accumulator, product, max, 7u16).unwrap();
+        assert_eq!(verify_at_key(accumulator, new_accumulator, max, 7u16, pi_i, pi_e), true);
+    }
+
+    #[test]
+    fn test_u128_does_not_fit() {
+        // The product of the primes of the 128 bit indices of a key overflows a U2048.
+        let product = (0..128).map(binary::index_to_prime)
+            .try_fold(U2048::from(1), |product, prime| product.checked_mul(prime));
+        assert_eq!(product, None);
+
+        // The 64 bit indices of a u64 key still fit.
+        let product = (0..64).map(binary::index_to_prime)
+            .try_fold(U2048::from(1), |product, prime| product.checked_mul(prime));
+        assert_eq!(product, Some(get_key_elem::<u64>(0)));
+    }
+
+    #[test]
+    fn test_empty_key() {
+        let accumulator: U2048 = U2048::from(2);
+        let (new_accumulator, product) = commit(accumulator, &[1], &[5u64]).unwrap();
+
+        let proof = prove_empty_key::<u64>(accumulator, product, 2);
+        assert_eq!(verify_empty_key::<u64>(accumulator, new_accumulator, 2, proof), true);
+
+        // A proof for the empty vector is not valid once the key has been committed.
+        let proof = prove_empty_key::<u64>(accumulator, U2048::from(1), 1);
+        assert_eq!(verify_empty_key::<u64>(accumulator, accumulator, 1, proof), true);
+        assert_eq!(verify_empty_key::<u64>(accumulator, new_accumulator, 1, proof), false);
+    }
+
+    #[test]
+    fn test_vc_open_and_verify_u64() {
+        let accumulator: U2048 = U2048::from(2);
+        let keys = vec![0, 1];
+        let values: Vec<u64> = vec![1000, 70000];
+        let (new_accumulator, product) = commit(accumulator, &keys, &values).unwrap();
 
-        assert_eq!(verify_at_key(accumulator, new_accumulator, 1, 7, pi_i, pi_e), true);
-        assert_eq!(verify_at_key(accumulator, new_accumulator, 0, 7, pi_i, pi_e), false);
-        assert_eq!(verify_at_key(accumulator, new_accumulator, 1, 4, pi_i, pi_e), false);
+        let (pi_i, pi_e) = open_at_key(accumulator, product, 1, 70000u64).unwrap();
+        assert_eq!(verify_at_key(accumulator, new_accumulator, 1, 70000u64, pi_i, pi_e), true);
+        assert_eq!(verify_at_key(accumulator, new_accumulator, 1, 70000u32, pi_i, pi_e), false);
     }
 
     #[test]
     fn test_get_key_value_elem() {
-        let (key, value): (usize, u8) = (0, 5);
+        let (key, value): (Key, u8) = (0, 5);
         let elem = get_key_value_elem(key, value);
 
         let bv = to_binary(value);
-        let indices: Vec<usize> = (0..8).collect();
-        let (state, _) = binary::commit(U2048::from(2), &bv, &indices);
+        let indices: Vec<Index> = (0..8).collect();
+        let (state, _) = binary::commit(U2048::from(2), &bv, &indices).unwrap();
 
         assert_eq!(state, subroutines::mod_exp(U2048::from(2), elem, U2048::from_dec_str(MODULUS).unwrap()))
     }